@@ -0,0 +1,400 @@
+//! a read-only http json api of the usage, authenticated by the api tokens of the users,
+//! only with the `api` feature
+
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Local, NaiveDate, TimeDelta};
+use dashmap::DashMap;
+use rand::RngCore;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, Set,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+
+use crate::{
+    model::{
+        api_token::{build_api_token_active, Column},
+        history, ApiTokenEntity, ApiTokenModel, ConfigEntity, DailyEntity, HistoryEntity,
+    },
+    utils::{
+        failure::FailureTracker,
+        history::{bucket_deltas, query_history, Granularity, UsageDelta},
+    },
+};
+
+type Tasks = Arc<DashMap<String, JoinHandle<()>>>;
+
+/// the address the api listens on
+#[derive(Clone, Debug)]
+pub struct ApiOptions {
+    pub addr: SocketAddr,
+}
+
+impl ApiOptions {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+}
+
+/// shared with the handler, so the api reads the same database and tasks
+#[derive(Clone)]
+pub struct ApiState {
+    pub db: sea_orm::DatabaseConnection,
+    pub tasks: Tasks,
+    pub failures: FailureTracker,
+}
+
+/// a new random token, `cu_` followed by 64 hex digits
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("cu_{}", hex::encode(bytes))
+}
+
+/// the hex sha256 of the token, only the hash is stored
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// create a token of the user, the token is returned only here as only the hash is stored
+pub async fn create_token(
+    db: &sea_orm::DatabaseConnection,
+    user: &str,
+    name: &str,
+) -> anyhow::Result<(ApiTokenModel, String)> {
+    let token = generate_token();
+    let token_active =
+        build_api_token_active(user.to_string(), name.to_string(), hash_token(&token));
+    let model = ApiTokenEntity::insert(token_active)
+        .exec_with_returning(db)
+        .await?;
+    Ok((model, token))
+}
+
+/// an error answered as `{"error": "..."}`
+pub struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn not_found(what: &str) -> Self {
+        Self(StatusCode::NOT_FOUND, format!("No {} yet.", what))
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(e: E) -> Self {
+        let e: anyhow::Error = e.into();
+        tracing::error!("ChinaUnicom: Api error: {}", e);
+        Self(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// the user of the bearer token of the request
+pub struct AuthUser(pub String);
+
+#[async_trait]
+impl FromRequestParts<ApiState> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &ApiState) -> Result<Self, ApiError> {
+        let unauthorized = || {
+            ApiError(
+                StatusCode::UNAUTHORIZED,
+                "A valid `Authorization: Bearer <token>` header is required.".to_string(),
+            )
+        };
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(unauthorized)?;
+        let model = ApiTokenEntity::find()
+            .filter(Column::TokenHash.eq(hash_token(token.trim())))
+            .one(&state.db)
+            .await?
+            .ok_or_else(unauthorized)?;
+        let user = model.user.clone();
+        let mut token_active = model.into_active_model();
+        token_active.last_used = Set(Some(Local::now()));
+        token_active.update(&state.db).await?;
+        Ok(Self(user))
+    }
+}
+
+/// the newest reading, the `last` row is only rewritten when a notification is sent
+async fn snapshot(
+    State(state): State<ApiState>,
+    AuthUser(user): AuthUser,
+) -> Result<Response, ApiError> {
+    let reading = HistoryEntity::find()
+        .filter(history::Column::User.eq(user))
+        .order_by_desc(history::Column::Time)
+        .one(&state.db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("snapshot"))?;
+    Ok(Json(reading).into_response())
+}
+
+async fn daily(
+    State(state): State<ApiState>,
+    AuthUser(user): AuthUser,
+) -> Result<Response, ApiError> {
+    let daily = DailyEntity::find_by_id(user)
+        .one(&state.db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("daily baseline"))?;
+    Ok(Json(daily).into_response())
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    /// default to 6 days before `to`
+    from: Option<NaiveDate>,
+    /// default to today
+    to: Option<NaiveDate>,
+    /// `hour` or `day`, default to `day`
+    granularity: Option<String>,
+}
+
+#[derive(Serialize)]
+struct HistoryBucket {
+    label: String,
+    #[serde(flatten)]
+    delta: UsageDelta,
+}
+
+async fn history(
+    State(state): State<ApiState>,
+    AuthUser(user): AuthUser,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Response, ApiError> {
+    let granularity = match query.granularity.as_deref() {
+        Some(granularity) => <Granularity as clap::ValueEnum>::from_str(granularity, true)
+            .map_err(|_| {
+                ApiError(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid granularity: {}", granularity),
+                )
+            })?,
+        None => Granularity::Day,
+    };
+    let to = query.to.unwrap_or_else(|| Local::now().date_naive());
+    let from = query.from.unwrap_or(to - TimeDelta::days(6));
+    if from > to {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            "`from` must not be after `to`.".to_string(),
+        ));
+    }
+    let (base, readings) = query_history(&state.db, &user, from, to).await?;
+    let buckets: Vec<HistoryBucket> = bucket_deltas(base.as_ref(), &readings, granularity)
+        .into_iter()
+        .map(|(label, delta)| HistoryBucket { label, delta })
+        .collect();
+    Ok(Json(json!({
+        "from": from,
+        "to": to,
+        "buckets": buckets,
+    }))
+    .into_response())
+}
+
+#[derive(Serialize)]
+struct TaskFailures {
+    total: u32,
+    consecutive: u32,
+    last_error: String,
+    last_time: DateTime<Local>,
+}
+
+#[derive(Serialize)]
+struct TaskStatus {
+    enabled: bool,
+    running: bool,
+    snooze_until: Option<DateTime<Local>>,
+    failures: Option<TaskFailures>,
+}
+
+async fn task(
+    State(state): State<ApiState>,
+    AuthUser(user): AuthUser,
+) -> Result<Response, ApiError> {
+    let config = ConfigEntity::find_by_id(user.as_str())
+        .one(&state.db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("config"))?;
+    let status = TaskStatus {
+        enabled: config.enable_task,
+        running: state
+            .tasks
+            .get(&user)
+            .is_some_and(|task| !task.is_finished()),
+        snooze_until: config.active_snooze(),
+        failures: state.failures.get(&user).map(|failure| TaskFailures {
+            total: failure.total,
+            consecutive: failure.consecutive,
+            last_error: failure.last_error,
+            last_time: failure.last_time,
+        }),
+    };
+    Ok(Json(status).into_response())
+}
+
+/// the routes under `/api`, every route needs a token
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/api/snapshot", get(snapshot))
+        .route("/api/daily", get(daily))
+        .route("/api/history", get(history))
+        .route("/api/task", get(task))
+        .with_state(state)
+}
+
+/// serve the api in the background
+pub fn serve(options: &ApiOptions, state: ApiState) {
+    let addr = options.addr;
+    let app = router(state);
+    tokio::spawn(async move {
+        let result = async {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            axum::serve(listener, app).await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::error!("ChinaUnicom: Api on {} failed: {}", addr, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chrono::Duration;
+    use dashmap::DashMap;
+    use sea_orm::EntityTrait;
+    use serde_json::Value;
+    use tokio::net::TcpListener;
+
+    use super::{create_token, router, ApiState};
+    use crate::{
+        model::ApiTokenEntity,
+        testing::{at, fake_context, insert_config, usage},
+        utils::china_unicom::query_once,
+    };
+
+    async fn json(response: reqwest::Response) -> Value {
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn read_usage() {
+        let (context, provider, _sink) = fake_context().await;
+        let config = insert_config(&context.db, "api_user").await;
+        insert_config(&context.db, "other_user").await;
+        // the last reading is below the thresholds, so only the history has it
+        provider
+            .push_usage(usage(at(1, 8, 0), 1.0))
+            .push_usage(usage(at(1, 9, 0), 1.5))
+            .push_usage(usage(at(1, 9, 10), 1.51));
+        for _ in 0..3 {
+            query_once(
+                &context.db,
+                context.provider.as_ref(),
+                &context.publishers,
+                &config,
+            )
+            .await
+            .unwrap();
+        }
+        context.failures.record("api_user", "network error");
+        let (model, token) = create_token(&context.db, "api_user", "dashboard")
+            .await
+            .unwrap();
+        assert!(token.starts_with("cu_"));
+        assert_ne!(model.token_hash, token);
+        let (_, other_token) = create_token(&context.db, "other_user", "dashboard")
+            .await
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/api", listener.local_addr().unwrap());
+        let app = router(ApiState {
+            db: context.db.clone(),
+            tasks: Arc::new(DashMap::new()),
+            failures: context.failures.clone(),
+        });
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let client = reqwest::Client::new();
+        let get = |path: &str, token: &str| {
+            client
+                .get(format!("{}{}", base, path))
+                .bearer_auth(token)
+                .send()
+        };
+
+        let response = get("/snapshot", "cu_invalid").await.unwrap();
+        assert_eq!(response.status(), 401);
+        let response = client
+            .get(format!("{}/snapshot", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let snapshot = json(get("/snapshot", &token).await.unwrap()).await;
+        assert_eq!(snapshot["user"], "api_user");
+        assert_eq!(snapshot["non_free_flow_used"], 1.51);
+        let daily = json(get("/daily", &token).await.unwrap()).await;
+        assert_eq!(daily["non_free_flow_used"], 1.0);
+        let response = get("/snapshot", &other_token).await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        let history = json(
+            get(
+                "/history?from=2026-10-01&to=2026-10-01&granularity=hour",
+                &token,
+            )
+            .await
+            .unwrap(),
+        )
+        .await;
+        let buckets = history["buckets"].as_array().unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[1]["label"], "2026-10-01 09:00");
+        assert!((buckets[1]["non_free_flow_used"].as_f64().unwrap() - 0.51).abs() < 1e-9);
+        let response = get("/history?granularity=minute", &token).await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let task = json(get("/task", &token).await.unwrap()).await;
+        assert_eq!(task["enabled"], config.enable_task);
+        assert_eq!(task["running"], false);
+        assert_eq!(task["failures"]["consecutive"], 1);
+        assert_eq!(task["failures"]["last_error"], "network error");
+
+        let model = ApiTokenEntity::find_by_id(model.id)
+            .one(&context.db)
+            .await
+            .unwrap()
+            .unwrap();
+        let last_used = model.last_used.unwrap();
+        assert!(chrono::Local::now() - last_used < Duration::minutes(1));
+    }
+}
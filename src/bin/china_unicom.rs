@@ -0,0 +1,295 @@
+//! track the usage without a chat bot, e.g. on a headless server or in cron jobs
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{Local, NaiveDate, TimeDelta};
+use clap::{Parser, Subcommand};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use china_unicom_oxidebot::{
+    model::{target, TargetEntity},
+    notifier::{ChatNotifier, Notifiers},
+    provider::ChinaUnicomProvider,
+    publisher::Publishers,
+    standalone::{list_targets, query, register, report, start_tasks, user_config, CommandSink},
+    utils::{
+        china_unicom::TaskContext,
+        credential::{extract_credentials, Credentials},
+        db::{connect_db, init_db},
+        failure::FailureTracker,
+        history::Granularity,
+    },
+};
+
+#[derive(Parser)]
+#[command(
+    name = "china_unicom",
+    version = "0.1.0",
+    about = "Check or receive scheduled/threshold notifications about China Unicom flow usage without a chat bot."
+)]
+struct Args {
+    /// The database url, default to `sqlite://./china_unicom/data.db`, the same as the bot
+    #[arg(long, global = true)]
+    db: Option<String>,
+    /// The user to act as
+    #[arg(long, short, global = true, default_value = "cli")]
+    user: String,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Register with the credentials of the China Unicom app
+    Register {
+        #[arg(long)]
+        cookie: Option<String>,
+        #[arg(long)]
+        app_id: Option<String>,
+        #[arg(long)]
+        token_online: Option<String>,
+        /// A file of a captured request of the app (curl command, raw HTTP request or HAR),
+        /// the credentials are extracted from it
+        #[arg(long)]
+        request: Option<std::path::PathBuf>,
+    },
+    /// Query data immediately and print it
+    Query {
+        /// Show which thresholds would fire, without updating or notifying
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the usage per hour or day
+    Report {
+        /// The first date, default to 6 days before `--to`
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// The last date, default to today
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        #[arg(long, value_enum, default_value_t = Granularity::Day)]
+        granularity: Granularity,
+        #[arg(long, default_value_t = 1)]
+        page: usize,
+    },
+    /// Manage where the notifications are delivered besides stdout or `--exec`
+    #[command(subcommand)]
+    Target(TargetCommand),
+    /// Run the tasks of the users registered here with the task enabled until interrupted
+    Daemon(DaemonArgs),
+}
+
+#[derive(clap::Args)]
+struct DaemonArgs {
+    /// Pipe each notification to the stdin of this shell command instead of printing it,
+    /// the user is in the `CHINA_UNICOM_USER` environment variable
+    #[arg(long)]
+    exec: Option<String>,
+    #[cfg(feature = "email")]
+    #[command(flatten)]
+    smtp: SmtpArgs,
+}
+
+#[derive(Subcommand)]
+enum TargetCommand {
+    /// List the targets
+    List,
+    /// Post the notifications to an http url as json
+    #[cfg(feature = "webhook")]
+    Webhook {
+        url: String,
+        /// Sign the body by HMAC-SHA256 with the secret
+        #[arg(long)]
+        secret: Option<String>,
+        /// Retry a failed post at most this times, at most 10
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(0..=10))]
+        retries: u32,
+    },
+    /// Send the notifications to an email address, needs the `--smtp-*` options of the daemon
+    #[cfg(feature = "email")]
+    Email { address: String },
+    /// Remove a target by id
+    Remove { id: i32 },
+}
+
+/// the smtp server delivering the email targets, email targets are skipped without it
+#[cfg(feature = "email")]
+#[derive(clap::Args)]
+struct SmtpArgs {
+    /// The smtp server delivering the email targets, they are skipped without it
+    #[arg(long)]
+    smtp_host: Option<String>,
+    /// Default to the port of the tls mode
+    #[arg(long)]
+    smtp_port: Option<u16>,
+    #[arg(long, value_enum, default_value_t = china_unicom_oxidebot::notifier::email::SmtpTls::Tls)]
+    smtp_tls: china_unicom_oxidebot::notifier::email::SmtpTls,
+    #[arg(long, requires = "smtp_password")]
+    smtp_username: Option<String>,
+    #[arg(long, requires = "smtp_username")]
+    smtp_password: Option<String>,
+    /// The sender, e.g. `ChinaUnicom <bot@example.com>`
+    #[arg(long, requires = "smtp_host")]
+    smtp_from: Option<String>,
+}
+
+#[cfg(feature = "email")]
+impl SmtpArgs {
+    fn options(self) -> Result<Option<china_unicom_oxidebot::notifier::email::SmtpOptions>> {
+        let Some(host) = self.smtp_host else {
+            return Ok(None);
+        };
+        let from = self.smtp_from.ok_or(anyhow::anyhow!(
+            "`--smtp-from` is required with `--smtp-host`."
+        ))?;
+        let mut options =
+            china_unicom_oxidebot::notifier::email::SmtpOptions::new(host, from).tls(self.smtp_tls);
+        if let Some(port) = self.smtp_port {
+            options = options.port(port);
+        }
+        if let (Some(username), Some(password)) = (self.smtp_username, self.smtp_password) {
+            options = options.credentials(username, password);
+        }
+        Ok(Some(options))
+    }
+}
+
+async fn manage_target(
+    db: &sea_orm::DatabaseConnection,
+    user: &str,
+    command: TargetCommand,
+) -> Result<()> {
+    user_config(db, user).await?;
+    match command {
+        TargetCommand::List => println!("{}", list_targets(db, user).await?),
+        #[cfg(feature = "webhook")]
+        TargetCommand::Webhook {
+            url,
+            secret,
+            retries,
+        } => {
+            // the operator of the command line is the admin
+            let options = china_unicom_oxidebot::notifier::webhook::WebhookOptions {
+                secret,
+                retries,
+                allow_private: true,
+            };
+            let target = china_unicom_oxidebot::notifier::webhook::add_webhook_target(
+                db, user, &url, &options,
+            )
+            .await?;
+            println!("Target added: {}", target.describe());
+        }
+        #[cfg(feature = "email")]
+        TargetCommand::Email { address } => {
+            let target =
+                china_unicom_oxidebot::notifier::email::add_email_target(db, user, &address)
+                    .await?;
+            println!("Target added: {}", target.describe());
+        }
+        TargetCommand::Remove { id } => {
+            let result = TargetEntity::delete_many()
+                .filter(target::Column::Id.eq(id))
+                .filter(target::Column::User.eq(user))
+                .exec(db)
+                .await?;
+            if result.rows_affected == 0 {
+                anyhow::bail!(
+                    "Target #{} not found, use `target list` to see your targets.",
+                    id
+                );
+            }
+            println!("Target #{} removed.", id);
+        }
+    }
+    Ok(())
+}
+
+async fn daemon(db: sea_orm::DatabaseConnection, args: DaemonArgs) -> Result<()> {
+    #[allow(unused_mut)]
+    let mut notifiers =
+        Notifiers::default().with(ChatNotifier::new(Arc::new(CommandSink { exec: args.exec })));
+    #[cfg(feature = "webhook")]
+    {
+        notifiers = notifiers.with(china_unicom_oxidebot::notifier::WebhookNotifier::new());
+    }
+    #[cfg(feature = "email")]
+    if let Some(smtp) = args.smtp.options()? {
+        notifiers = notifiers.with(china_unicom_oxidebot::notifier::EmailNotifier::new(&smtp)?);
+    }
+    let context = TaskContext {
+        db,
+        provider: Arc::new(ChinaUnicomProvider),
+        notifiers,
+        publishers: Publishers::default(),
+        failures: FailureTracker::default(),
+    };
+    let mut tasks = start_tasks(context).await?;
+    if tasks.is_empty() {
+        anyhow::bail!("No task is running.");
+    }
+    eprintln!("{} task(s) running, press Ctrl-C to stop.", tasks.len());
+
+    let all_stopped = async {
+        for task in &mut tasks {
+            let _ = task.await;
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = all_stopped => anyhow::bail!("All tasks stopped after repeated query failures."),
+    }
+    for task in tasks {
+        task.abort();
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let db = match &args.db {
+        Some(url) => connect_db(url).await?,
+        None => init_db().await?,
+    };
+    let user = args.user.as_str();
+
+    match args.command {
+        Commands::Register {
+            cookie,
+            app_id,
+            token_online,
+            request,
+        } => {
+            let mut credentials = match request {
+                Some(path) => extract_credentials(&std::fs::read_to_string(path)?)
+                    .ok_or(anyhow::anyhow!("No credentials found in the request."))?,
+                None => Credentials::default(),
+            };
+            credentials.cookie = cookie.or(credentials.cookie);
+            credentials.app_id = app_id.or(credentials.app_id);
+            credentials.token_online = token_online.or(credentials.token_online);
+            println!(
+                "{}",
+                register(&db, &ChinaUnicomProvider, user, credentials).await?
+            );
+        }
+        Commands::Query { dry_run } => {
+            println!("{}", query(&db, &ChinaUnicomProvider, user, dry_run).await?);
+        }
+        Commands::Report {
+            from,
+            to,
+            granularity,
+            page,
+        } => {
+            let to = to.unwrap_or_else(|| Local::now().date_naive());
+            let from = from.unwrap_or(to - TimeDelta::days(6));
+            println!("{}", report(&db, user, from, to, granularity, page).await?);
+        }
+        Commands::Target(command) => manage_target(&db, user, command).await?,
+        Commands::Daemon(args) => daemon(db, args).await?,
+    }
+    Ok(())
+}
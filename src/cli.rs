@@ -1,4 +1,11 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+use chrono::NaiveDate;
+
+use crate::{
+    model::settings::{self, Setting},
+    utils::{export::ExportFormat, history::Granularity, import::ImportMode},
+};
 
 #[derive(Subcommand)]
 pub enum TaskCommand {
@@ -8,15 +15,150 @@ pub enum TaskCommand {
     Stop,
     /// Check the status of the task
     Status,
+    /// Send a sample notification the same way as the task
+    Test,
+    /// Keep querying and recording but suppress notifications, e.g. `snooze 2h` or `snooze until 08:00`
+    Snooze {
+        #[arg(required = true, num_args = 1..)]
+        time: Vec<String>,
+    },
+    /// Resume the snoozed notifications
+    Unsnooze,
 }
 
 #[derive(Subcommand)]
 pub enum ConfigCommand {
-    /// Show configuration
-    Show,
+    /// Show configuration, secrets are masked
+    Show {
+        /// Output as json
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Set configuration, use 'None' to set null.
+    /// Start an interactive wizard if no key is given
+    Set {
+        /// The setting to set, see the settings list below
+        #[arg(value_parser = settings::parse_setting_name)]
+        key: Option<&'static Setting>,
+        /// The new value, will be asked for if not given
+        value: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum GroupCommand {
+    /// Also send your notifications to the current group
+    Subscribe,
+    /// Stop sending your notifications to the current group
+    Unsubscribe,
+    /// List the groups you subscribed
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum TargetCommand {
+    /// List where your notifications are delivered
+    List,
+    /// Post your usage as json to a webhook
+    #[cfg(feature = "webhook")]
+    Webhook {
+        /// The http or https url
+        url: String,
+        /// Sign the body with HMAC-SHA256, in the `X-ChinaUnicom-Signature` header
+        #[arg(long)]
+        secret: Option<String>,
+        /// The retries after the first attempt failed, at most 10
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(0..=10))]
+        retries: u32,
+    },
+    /// Send your notifications to an email address
+    #[cfg(feature = "email")]
+    Email {
+        /// The email address
+        address: String,
+    },
+    /// Deliver notifications to the target again
+    Enable {
+        /// The id of the target, shown in `target list`
+        id: i32,
+    },
+    /// Stop delivering notifications to the target
+    Disable {
+        /// The id of the target, shown in `target list`
+        id: i32,
+    },
+    /// Remove the target
+    Remove {
+        /// The id of the target, shown in `target list`
+        id: i32,
+    },
+}
 
-    /// Set configuration, use 'None' to set null
-    Set,
+#[cfg(feature = "api")]
+#[derive(Subcommand)]
+pub enum TokenCommand {
+    /// Create a token to read your usage by the http api, it is shown only once
+    Create {
+        /// A name to tell the tokens apart
+        #[arg(default_value = "default")]
+        name: String,
+    },
+    /// List your tokens
+    List,
+    /// Revoke a token
+    Revoke {
+        /// The id of the token, shown in `token list`
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminCommand {
+    /// List registered users with their task state
+    List,
+    /// Start the task of a user
+    Start {
+        /// The user, in the form of `{server}_{user_id}`
+        user: String,
+    },
+    /// Stop the task of a user
+    Stop {
+        /// The user, in the form of `{server}_{user_id}`
+        user: String,
+    },
+    /// Query data of a user immediately
+    Query {
+        /// The user, in the form of `{server}_{user_id}`
+        user: String,
+    },
+    /// Show the query failures of tasks
+    Failures,
+    /// Post the usage of a user as json to a webhook
+    #[cfg(feature = "webhook")]
+    Webhook {
+        /// The user, in the form of `{server}_{user_id}`
+        user: String,
+        /// The http or https url
+        url: String,
+        /// Sign the body with HMAC-SHA256, in the `X-ChinaUnicom-Signature` header
+        #[arg(long)]
+        secret: Option<String>,
+        /// The retries after the first attempt failed, at most 10
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(0..=10))]
+        retries: u32,
+    },
+    /// Remove a user and all the data
+    Remove {
+        /// The user, in the form of `{server}_{user_id}`
+        user: String,
+    },
+    /// Import the data exported by `export --with-secrets`, a json list of exports is also accepted,
+    /// a long export can be pasted in several messages
+    Import {
+        #[arg(long, value_enum, default_value_t = ImportMode::Merge)]
+        mode: ImportMode,
+    },
 }
 
 #[derive(Subcommand)]
@@ -38,7 +180,35 @@ pub enum Commands {
 
     /// Query data immediately
     #[command(short_flag = 'q')]
-    Query,
+    Query {
+        /// Show which thresholds would fire now and why, without updating the last data
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show the usage of each hour or day in a date range
+    History {
+        /// The first date, e.g. 2026-10-01, default to 7 days ago
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// The last date (included), default to today
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        #[arg(long, value_enum, default_value_t = Granularity::Day)]
+        granularity: Granularity,
+        /// The page to show
+        #[arg(long, default_value_t = 1)]
+        page: usize,
+    },
+
+    /// Export your config, snapshots and history
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Include the cookie and tokens, needed to import into another server
+        #[arg(long)]
+        with_secrets: bool,
+    },
 
     /// Check or control task
     #[command(short_flag = 't')]
@@ -46,6 +216,47 @@ pub enum Commands {
         #[command(subcommand)]
         task_command: TaskCommand,
     },
+
+    /// Send your notifications to groups
+    #[command(short_flag = 'g')]
+    Group {
+        #[command(subcommand)]
+        group_command: GroupCommand,
+    },
+
+    /// Manage where your notifications are delivered
+    Target {
+        #[command(subcommand)]
+        target_command: TargetCommand,
+    },
+
+    /// Manage the tokens of the http api
+    #[cfg(feature = "api")]
+    Token {
+        #[command(subcommand)]
+        token_command: TokenCommand,
+    },
+
+    /// Commands for admins
+    Admin {
+        #[command(subcommand)]
+        admin_command: AdminCommand,
+    },
+}
+
+impl Commands {
+    /// whether the command is safe to use in groups, commands about credentials are private only
+    pub fn allowed_in_group(&self) -> bool {
+        matches!(
+            self,
+            Commands::Query { .. }
+                | Commands::History { .. }
+                | Commands::Task {
+                    task_command: TaskCommand::Status
+                }
+                | Commands::Group { .. }
+        )
+    }
 }
 
 #[derive(Parser)]
@@ -64,4 +275,17 @@ impl Cli {
     pub fn name() -> &'static str {
         "/china_unicom"
     }
+
+    /// parse the args with the settings list added to the help of `config set`
+    pub fn try_parse_with_settings<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let command = Cli::command().mut_subcommand("config", |config| {
+            config.mut_subcommand("set", |set| set.after_help(settings::help()))
+        });
+        let mut matches = command.try_get_matches_from(args)?;
+        Cli::from_arg_matches_mut(&mut matches)
+    }
 }
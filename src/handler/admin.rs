@@ -0,0 +1,331 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use oxidebot::{matcher::Matcher, wait_user_text_generic, EasyBool};
+use sea_orm::{EntityTrait, IntoActiveModel, Set};
+
+use crate::{
+    cli::AdminCommand,
+    model::{ConfigActiveModel, ConfigEntity},
+    utils::{
+        china_unicom::query_once,
+        import::{
+            import_user, parse_import, ImportChunks, ImportMode, IMPORT_END_MARKER, IMPORT_MAX_SIZE,
+        },
+    },
+    ChinaUnicomHandler,
+};
+
+impl ChinaUnicomHandler {
+    pub(crate) async fn handle_admin(
+        &self,
+        matcher: &Matcher,
+        command: AdminCommand,
+    ) -> Result<()> {
+        match command {
+            AdminCommand::List => self.handle_admin_list(matcher).await,
+            AdminCommand::Start { user } => self.handle_admin_start(matcher, &user).await,
+            AdminCommand::Stop { user } => self.handle_admin_stop(matcher, &user).await,
+            AdminCommand::Query { user } => self.handle_admin_query(matcher, &user).await,
+            AdminCommand::Failures => self.handle_admin_failures(matcher).await,
+            #[cfg(feature = "webhook")]
+            AdminCommand::Webhook {
+                user,
+                url,
+                secret,
+                retries,
+            } => {
+                let options = crate::notifier::webhook::WebhookOptions {
+                    secret,
+                    retries,
+                    allow_private: true,
+                };
+                self.handle_admin_webhook(matcher, &user, &url, &options)
+                    .await
+            }
+            AdminCommand::Remove { user } => self.handle_admin_remove(matcher, &user).await,
+            AdminCommand::Import { mode } => self.handle_admin_import(matcher, mode).await,
+        }
+    }
+
+    #[cfg(feature = "webhook")]
+    async fn handle_admin_webhook(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        url: &str,
+        options: &crate::notifier::webhook::WebhookOptions,
+    ) -> Result<()> {
+        if ConfigEntity::find_by_id(user)
+            .one(&self.db)
+            .await?
+            .is_none()
+        {
+            self.send_message(matcher, &format!("User {} is not registered.", user))
+                .await?;
+            return Ok(());
+        }
+        self.handle_add_webhook(matcher, user, url, options).await
+    }
+
+    /// the state of the task of the user
+    fn task_state(&self, user: &str) -> &'static str {
+        match self.tasks.get(user) {
+            Some(task) if !task.is_finished() => "running",
+            Some(_) => "exited",
+            None => "not running",
+        }
+    }
+
+    async fn handle_admin_list(&self, matcher: &Matcher) -> Result<()> {
+        let configs = ConfigEntity::find().all(&self.db).await?;
+        if configs.is_empty() {
+            self.send_message(matcher, "No registered users.").await?;
+            return Ok(());
+        }
+        let mut lines = vec![format!("{} registered users:", configs.len())];
+        for config in configs {
+            let mut line = format!(
+                "{}: task {}, {}",
+                config.user,
+                self.task_state(&config.user),
+                if config.enable_task {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+            if let Some(failure) = self.failures.get(&config.user) {
+                line += &format!(
+                    ", {} failures ({} consecutive)",
+                    failure.total, failure.consecutive
+                );
+            }
+            lines.push(line);
+        }
+        self.send_message(matcher, &lines.join("\n")).await?;
+        Ok(())
+    }
+
+    /// set `enable_task` of the user, return false if the user is not registered
+    async fn set_task_enabled(&self, user: &str, enable: bool) -> Result<bool> {
+        let Some(config) = ConfigEntity::find_by_id(user).one(&self.db).await? else {
+            return Ok(false);
+        };
+        if config.enable_task != enable {
+            let mut config_active: ConfigActiveModel = config.into_active_model();
+            config_active.enable_task = Set(enable);
+            ConfigEntity::update(config_active).exec(&self.db).await?;
+        }
+        Ok(true)
+    }
+
+    async fn handle_admin_start(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        if !self.set_task_enabled(user, true).await? {
+            self.send_message(matcher, &format!("User {} is not registered.", user))
+                .await?;
+            return Ok(());
+        }
+        match self.restart_user_task(user, true).await {
+            Ok(_) => {
+                self.send_message(matcher, &format!("Task of {} started.", user))
+                    .await?;
+            }
+            Err(e) => {
+                self.send_message(matcher, &format!("Task of {} start failed: {:?}", user, e))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_admin_stop(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        if !self.set_task_enabled(user, false).await? {
+            self.send_message(matcher, &format!("User {} is not registered.", user))
+                .await?;
+            return Ok(());
+        }
+        if let Some((_user, task)) = self.tasks.remove(user) {
+            task.abort();
+            self.send_message(matcher, &format!("Task of {} stopped.", user))
+                .await?;
+        } else {
+            self.send_message(matcher, &format!("Task of {} is not running.", user))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_admin_query(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        let Some(config) = ConfigEntity::find_by_id(user).one(&self.db).await? else {
+            self.send_message(matcher, &format!("User {} is not registered.", user))
+                .await?;
+            return Ok(());
+        };
+        match query_once(&self.db, self.provider.as_ref(), &self.publishers, &config).await {
+            Ok((_should_send, notification)) => {
+                self.failures.success(user);
+                self.send_message(matcher, &format!("{}:\n{}", user, notification.text))
+                    .await?;
+            }
+            Err(e) => {
+                self.failures.record(user, &e.to_string());
+                self.send_message(
+                    matcher,
+                    &format!("An error occurred while querying {}: {:?}", user, e),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_admin_failures(&self, matcher: &Matcher) -> Result<()> {
+        let failures = self.failures.all();
+        if failures.is_empty() {
+            self.send_message(matcher, "No failures since start.")
+                .await?;
+            return Ok(());
+        }
+        let mut lines = Vec::new();
+        for (user, failure) in failures {
+            lines.push(format!(
+                "{} (task {}): {} failures, {} consecutive, last at {}:\n  {}",
+                user,
+                self.task_state(&user),
+                failure.total,
+                failure.consecutive,
+                failure.last_time.format("%Y-%m-%d %H:%M:%S"),
+                failure.last_error
+            ));
+        }
+        self.send_message(matcher, &lines.join("\n")).await?;
+        Ok(())
+    }
+
+    async fn handle_admin_remove(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        if ConfigEntity::find_by_id(user)
+            .one(&self.db)
+            .await?
+            .is_none()
+        {
+            self.send_message(matcher, &format!("User {} is not registered.", user))
+                .await?;
+            return Ok(());
+        }
+        self.send_message(
+            matcher,
+            &format!(
+                "Are you sure you want to remove {} and all the data?\nSend 'y' to confirm, 'n' to cancel.",
+                user
+            ),
+        )
+        .await?;
+        let (easy_bool, matcher) = wait_user_text_generic::<EasyBool>(
+            matcher,
+            &self.broadcast_sender,
+            Duration::from_secs(30),
+            1,
+            None,
+        )
+        .await?;
+
+        // cancel
+        if !easy_bool.0 {
+            return Ok(());
+        }
+
+        match self.remove_user(user).await {
+            Ok(_) => {
+                self.send_message(&matcher, &format!("User {} removed.", user))
+                    .await?;
+            }
+            Err(e) => {
+                self.send_message(
+                    &matcher,
+                    &format!("An error occurred while removing {}: {}", user, e),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_admin_import(&self, matcher: &Matcher, mode: ImportMode) -> Result<()> {
+        self.send_message(
+            matcher,
+            &format!(
+                "Please send the exported json, at most {} KB. A long export can be sent in several messages, each in 60s, then send `{}` if it is not detected as complete.",
+                IMPORT_MAX_SIZE / 1024,
+                IMPORT_END_MARKER
+            ),
+        )
+        .await?;
+        let mut chunks = ImportChunks::default();
+        let mut matcher = matcher.clone();
+        loop {
+            let (text, new_matcher) = wait_user_text_generic::<String>(
+                &matcher,
+                &self.broadcast_sender,
+                Duration::from_secs(60),
+                1,
+                None,
+            )
+            .await?;
+            matcher = new_matcher;
+            match chunks.push(&text) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => {
+                    self.send_message(&matcher, &e).await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        let exports = match parse_import(chunks.text()) {
+            Ok(exports) => exports,
+            Err(e) => {
+                self.send_message(&matcher, &e).await?;
+                return Ok(());
+            }
+        };
+
+        let mut lines = Vec::new();
+        for export in exports {
+            match import_user(&self.db, export, mode).await {
+                Ok(report) => {
+                    let task = self
+                        .restart_user_task(&report.user, report.enable_task)
+                        .await;
+                    lines.push(format!(
+                        "{}: last {}, daily {}, {} history readings, task: {}",
+                        report.user,
+                        if report.last { "imported" } else { "kept" },
+                        if report.daily { "imported" } else { "kept" },
+                        report.history,
+                        match task {
+                            Ok(true) => "started".to_string(),
+                            Ok(false) => "disabled".to_string(),
+                            Err(e) => format!("start failed: {}", e),
+                        }
+                    ));
+                }
+                Err(e) => lines.push(format!("Import failed: {}", e)),
+            }
+        }
+        self.send_message(&matcher, &lines.join("\n")).await?;
+        Ok(())
+    }
+
+    /// stop the running task of the user, and start a new one if `enable`
+    async fn restart_user_task(&self, user: &str, enable: bool) -> Result<bool> {
+        if let Some((_user, task)) = self.tasks.remove(user) {
+            task.abort();
+        }
+        if enable {
+            self.add_task(user).await?;
+        }
+        Ok(enable)
+    }
+}
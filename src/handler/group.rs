@@ -0,0 +1,115 @@
+use anyhow::Result;
+use oxidebot::matcher::Matcher;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, SqlErr};
+
+use crate::{
+    cli::GroupCommand,
+    model::{
+        target::{build_target_active, Column, TargetKind},
+        TargetEntity,
+    },
+    utils::oxidebot_util::get_group_from,
+    ChinaUnicomHandler,
+};
+
+impl ChinaUnicomHandler {
+    pub(crate) async fn handle_group(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        bot: &str,
+        command: GroupCommand,
+    ) -> Result<()> {
+        if self.get_user_config(matcher).await?.is_none() {
+            return Ok(());
+        }
+        match command {
+            GroupCommand::Subscribe => self.handle_group_subscribe(matcher, user, bot).await,
+            GroupCommand::Unsubscribe => self.handle_group_unsubscribe(matcher, user).await,
+            GroupCommand::List => self.handle_group_list(matcher, user).await,
+        }
+    }
+
+    /// get the current group, if not in a group, send message to user
+    async fn current_group(&self, matcher: &Matcher) -> Result<Option<String>> {
+        if !self.options.groups {
+            self.send_message(matcher, "Group support is not enabled on this bot.")
+                .await?;
+            return Ok(None);
+        }
+        let group = get_group_from(matcher);
+        if group.is_none() {
+            self.send_message(matcher, "Please use this command in the group.")
+                .await?;
+        }
+        Ok(group)
+    }
+
+    async fn handle_group_subscribe(&self, matcher: &Matcher, user: &str, bot: &str) -> Result<()> {
+        let Some(group) = self.current_group(matcher).await? else {
+            return Ok(());
+        };
+        let target =
+            build_target_active(user.to_string(), TargetKind::Group, group, bot.to_string());
+        match TargetEntity::insert(target).exec(&self.db).await {
+            Ok(_) => {
+                self.send_message(
+                    matcher,
+                    "Subscribe success, your notifications will also be sent to this group.",
+                )
+                .await?;
+            }
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                self.send_message(matcher, "You have already subscribed this group.")
+                    .await?;
+            }
+            Err(e) => {
+                self.send_message(
+                    matcher,
+                    &format!("An error occurred while subscribing: {:?}", e),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_group_unsubscribe(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        let Some(group) = self.current_group(matcher).await? else {
+            return Ok(());
+        };
+        let result = TargetEntity::delete_many()
+            .filter(Column::User.eq(user))
+            .filter(Column::Kind.eq(TargetKind::Group))
+            .filter(Column::Address.eq(group))
+            .exec(&self.db)
+            .await?;
+        if result.rows_affected > 0 {
+            self.send_message(matcher, "Unsubscribe success.").await?;
+        } else {
+            self.send_message(matcher, "You have not subscribed this group.")
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_group_list(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        let targets = TargetEntity::find()
+            .filter(Column::User.eq(user))
+            .filter(Column::Kind.eq(TargetKind::Group))
+            .all(&self.db)
+            .await?;
+        if targets.is_empty() {
+            self.send_message(matcher, "You have not subscribed any group.")
+                .await?;
+            return Ok(());
+        }
+        let groups: Vec<String> = targets.iter().map(|target| target.describe()).collect();
+        self.send_message(
+            matcher,
+            &format!("Subscribed groups:\n{}", groups.join("\n")),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1020 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::{Local, NaiveDate, TimeDelta};
+use dashmap::DashMap;
+use oxidebot::{
+    handler::Handler, manager::BroadcastSender, matcher::Matcher, source::message::MessageSegment,
+    wait_user_text_generic, EasyBool, EventHandlerTrait,
+};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set};
+use tokio::task::JoinHandle;
+
+use crate::{
+    cli::{self, Cli},
+    model::{
+        settings::{self, Setting},
+        target::{ensure_private_target, Column as TargetColumn},
+        ConfigActiveModel, ConfigEntity, ConfigModel, TargetEntity,
+    },
+    notifier::{ChatNotifier, Notification, Notifiers},
+    options::HandlerOptions,
+    provider::{ChinaUnicomProvider, SharedProvider},
+    publisher::Publishers,
+    utils::{
+        china_unicom::{
+            create_china_unicom_task, query_dry_run, query_once, verify_credentials, TaskContext,
+        },
+        credential::{extract_credentials, normalize_cookie, Credentials},
+        db::{delete_user, init_db},
+        export::{build_export, chunk_text, ExportFormat, TEXT_CHUNK_SIZE},
+        failure::FailureTracker,
+        history::{bucket_deltas, format_history_page, query_history, Granularity},
+        oxidebot_util::{get_user_bot_from, send_file},
+        redact::mask_secret,
+        sink::OxidebotSink,
+        snooze::parse_snooze,
+    },
+};
+mod admin;
+mod group;
+mod target;
+#[cfg(feature = "api")]
+mod token;
+
+pub struct ChinaUnicomHandler {
+    pub db: sea_orm::DatabaseConnection,
+    pub tasks: Arc<DashMap<String, JoinHandle<()>>>,
+    pub broadcast_sender: BroadcastSender,
+    pub options: HandlerOptions,
+    pub failures: FailureTracker,
+    pub provider: SharedProvider,
+    pub notifiers: Notifiers,
+    pub publishers: Publishers,
+}
+
+impl ChinaUnicomHandler {
+    pub async fn new(broadcast_sender: BroadcastSender) -> Handler {
+        Self::with_options(broadcast_sender, HandlerOptions::default()).await
+    }
+
+    pub async fn with_options(
+        broadcast_sender: BroadcastSender,
+        options: HandlerOptions,
+    ) -> Handler {
+        Self::with_provider(broadcast_sender, options, Arc::new(ChinaUnicomProvider)).await
+    }
+
+    /// use another source of the usage data instead of the China Unicom api
+    pub async fn with_provider(
+        broadcast_sender: BroadcastSender,
+        options: HandlerOptions,
+        provider: SharedProvider,
+    ) -> Handler {
+        #[allow(unused_mut)]
+        let mut notifiers = Notifiers::default().with(ChatNotifier::new(Arc::new(OxidebotSink)));
+        #[cfg(feature = "webhook")]
+        {
+            notifiers = notifiers.with(crate::notifier::WebhookNotifier::new());
+        }
+        #[cfg(feature = "email")]
+        if let Some(smtp) = &options.smtp {
+            match crate::notifier::EmailNotifier::new(smtp) {
+                Ok(notifier) => notifiers = notifiers.with(notifier),
+                Err(e) => tracing::error!("ChinaUnicom: Invalid smtp options: {}", e),
+            }
+        }
+        #[allow(unused_mut)]
+        let mut publishers = Publishers::default();
+        #[cfg(feature = "mqtt")]
+        if let Some(mqtt) = &options.mqtt {
+            publishers = publishers.with(crate::publisher::MqttPublisher::new(mqtt.clone()));
+        }
+        #[cfg(feature = "metrics")]
+        let provider: SharedProvider = match &options.metrics {
+            Some(metrics_options) => {
+                if metrics_options.per_user {
+                    publishers = publishers.with(crate::metrics::MetricsPublisher);
+                }
+                Arc::new(crate::metrics::MeteredProvider(provider))
+            }
+            None => provider,
+        };
+        let self_ = Self {
+            db: init_db().await.unwrap(),
+            tasks: Arc::new(DashMap::new()),
+            broadcast_sender,
+            options,
+            failures: FailureTracker::default(),
+            provider,
+            notifiers,
+            publishers,
+        };
+        #[cfg(feature = "metrics")]
+        if let Some(metrics_options) = &self_.options.metrics {
+            crate::metrics::serve(metrics_options, self_.tasks.clone());
+        }
+        #[cfg(feature = "api")]
+        if let Some(api_options) = &self_.options.api {
+            crate::api::serve(
+                api_options,
+                crate::api::ApiState {
+                    db: self_.db.clone(),
+                    tasks: self_.tasks.clone(),
+                    failures: self_.failures.clone(),
+                },
+            );
+        }
+        self_.start_all_tasks().await.unwrap();
+        Handler {
+            event_handler: Some(Box::new(self_)),
+            active_handler: None,
+        }
+    }
+}
+
+impl ChinaUnicomHandler {
+    async fn start_all_tasks(&self) -> Result<()> {
+        let configs = crate::standalone::bot_configs(&self.db).await?;
+        for mut config in configs {
+            let repaired = config.repair();
+            if !repaired.is_empty() {
+                tracing::warn!(
+                    "ChinaUnicom: Repaired invalid config of user {}: {}",
+                    config.user,
+                    repaired.join(", ")
+                );
+                let config_active = config.clone().into_active_model().reset_all();
+                ConfigEntity::update(config_active).exec(&self.db).await?;
+            }
+            let tasks = self.tasks.clone();
+            let context = self.task_context();
+            tokio::spawn(async move {
+                let db = context.db.clone();
+                let notifiers = context.notifiers.clone();
+                match create_china_unicom_task(context, config.user.clone()).await {
+                    Ok(task) => {
+                        tasks.insert(config.user, task);
+                    }
+                    Err(e) => {
+                        tracing::error!("ChinaUnicom: Task Auto start failed: {:?}", e);
+                        let notification = Notification::new(
+                            &config.user,
+                            format!("ChinaUnicom: Task Auto start failed: {:?}", e),
+                        );
+                        let _ = notifiers.dispatch(&db, &notification).await;
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn task_context(&self) -> TaskContext {
+        TaskContext {
+            db: self.db.clone(),
+            provider: self.provider.clone(),
+            notifiers: self.notifiers.clone(),
+            publishers: self.publishers.clone(),
+            failures: self.failures.clone(),
+        }
+    }
+
+    async fn send_message(&self, matcher: &Matcher, text: &str) -> Result<()> {
+        matcher
+            .try_send_message(vec![MessageSegment::text(text.to_string())])
+            .await?;
+        Ok(())
+    }
+    /// get user config, if not registered, send message to user
+    async fn get_user_config(&self, matcher: &Matcher) -> Result<Option<ConfigModel>> {
+        if let Some((user, _bot)) = get_user_bot_from(matcher).await {
+            let config = ConfigEntity::find_by_id(user).one(&self.db).await?;
+            if config.is_none() {
+                let _ =  self.send_message(matcher, "You have not registered yet, please use the `register` command to register first.").await;
+            }
+            return Ok(config);
+        }
+        Ok(None)
+    }
+
+    /// ask the user for the value of a credential
+    async fn ask_text(&self, matcher: &Matcher, name: &str) -> Result<(String, Matcher)> {
+        self.send_message(
+            matcher,
+            &format!("Please send your China Unicom {} in 30s.", name),
+        )
+        .await?;
+        wait_user_text_generic::<String>(
+            matcher,
+            &self.broadcast_sender,
+            Duration::from_secs(30),
+            1,
+            None,
+        )
+        .await
+    }
+
+    /// ask the user to confirm with 'y' or 'n'
+    async fn confirm(&self, matcher: &Matcher, text: &str) -> Result<(bool, Matcher)> {
+        self.send_message(matcher, text).await?;
+        let (easy_bool, matcher) = wait_user_text_generic::<EasyBool>(
+            matcher,
+            &self.broadcast_sender,
+            Duration::from_secs(30),
+            1,
+            None,
+        )
+        .await?;
+        Ok((easy_bool.0, matcher))
+    }
+
+    async fn handle_register(&self, matcher: &Matcher, user: &str, bot: &str) -> Result<()> {
+        let _ = matcher
+            .try_send_message(vec![MessageSegment::text(
+                "Please send your China Unicom Cookie in 30s.\nYou can also paste a captured request of the China Unicom app (curl command, raw HTTP request or HAR), the Cookie, AppId and TokenOnline will be extracted from it.".to_string(),
+            )])
+            .await?;
+
+        let (text, mut matcher) = wait_user_text_generic::<String>(
+            matcher,
+            &self.broadcast_sender,
+            Duration::from_secs(30),
+            1,
+            None,
+        )
+        .await?;
+
+        let captured = extract_credentials(&text);
+        let mut credentials = match &captured {
+            Some(credentials) => {
+                self.send_message(
+                    &matcher,
+                    &format!("Found in the pasted request:\n{}", credentials.summary()),
+                )
+                .await?;
+                credentials.clone()
+            }
+            None => Credentials {
+                cookie: Some(normalize_cookie(&text)),
+                ..Default::default()
+            },
+        };
+
+        if credentials.cookie.is_none() {
+            let (cookie, new_matcher) = self.ask_text(&matcher, "Cookie").await?;
+            credentials.cookie = Some(normalize_cookie(&cookie));
+            matcher = new_matcher;
+        }
+        if credentials.app_id.is_none() {
+            let (app_id, new_matcher) = self.ask_text(&matcher, "AppId").await?;
+            credentials.app_id = Some(app_id.trim().to_string());
+            matcher = new_matcher;
+        }
+        if credentials.token_online.is_none() {
+            let (token_online, new_matcher) = self.ask_text(&matcher, "TokenOnline").await?;
+            credentials.token_online = Some(token_online.trim().to_string());
+            matcher = new_matcher;
+        }
+
+        // confirm the extracted credentials before saving
+        if captured.is_some() {
+            let (confirmed, new_matcher) = self
+                .confirm(
+                    &matcher,
+                    &format!(
+                        "Register with these credentials?\n{}\nSend 'y' to confirm, 'n' to cancel.",
+                        credentials.summary()
+                    ),
+                )
+                .await?;
+            matcher = new_matcher;
+            if !confirmed {
+                self.send_message(&matcher, "Register cancel.").await?;
+                return Ok(());
+            }
+        }
+
+        let mut config = ConfigModel {
+            user: user.to_string(),
+            bot: bot.to_string(),
+            ..Default::default()
+        };
+        credentials.apply(&mut config);
+
+        if let Err(errors) = config.validate() {
+            self.send_message(
+                &matcher,
+                &format!("Register failed:\n{}", errors.join("\n")),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        self.send_message(&matcher, "Verifying your credentials, please wait...")
+            .await?;
+        let (config, data) = match verify_credentials(self.provider.as_ref(), config).await {
+            Ok(result) => result,
+            Err(e) => {
+                self.send_message(
+                    &matcher,
+                    &format!("Register failed, nothing is saved.\n{}", e),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let config_active: ConfigActiveModel = config.into();
+        match ConfigEntity::insert(config_active).exec(&self.db).await {
+            Ok(_) => {
+                ensure_private_target(&self.db, user, bot).await?;
+                self.send_message(
+                    &matcher,
+                    &format!("Register success, your package: {}.\nYour task will be automatically started, you can use the `task` command to view the status of the task or control it.", data.package_name),
+                )
+                .await?;
+                self.handle_add_task(&matcher, user).await?;
+            }
+            Err(sea_orm::DbErr::RecordNotInserted) => {
+                self.send_message(
+                    &matcher,
+                    "You have already registered, if you want to update your cookie, please use the `set` command.",
+                )
+                .await?;
+            }
+            Err(e) => {
+                self.send_message(
+                    &matcher,
+                    &format!("An error occurred while registering: {:?}", e),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_query(&self, matcher: &Matcher, dry_run: bool) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            if dry_run {
+                match query_dry_run(&self.db, self.provider.as_ref(), &config).await {
+                    Ok(message) => self.send_message(matcher, &message).await?,
+                    Err(e) => {
+                        self.send_message(
+                            matcher,
+                            &format!("An error occurred while querying: {:?}", e),
+                        )
+                        .await?
+                    }
+                }
+                return Ok(());
+            }
+            match query_once(&self.db, self.provider.as_ref(), &self.publishers, &config).await {
+                Ok((_should_send, notification)) => {
+                    self.send_message(matcher, &notification.text).await?;
+                }
+                Err(e) => {
+                    self.send_message(
+                        matcher,
+                        &format!("An error occurred while querying: {:?}", e),
+                    )
+                    .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_history(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        granularity: Granularity,
+        page: usize,
+    ) -> Result<()> {
+        if self.get_user_config(matcher).await?.is_none() {
+            return Ok(());
+        }
+        let to = to.unwrap_or_else(|| Local::now().date_naive());
+        let from = from.unwrap_or(to - TimeDelta::days(6));
+        if from > to {
+            self.send_message(
+                matcher,
+                "The `--from` date must not be after the `--to` date.",
+            )
+            .await?;
+            return Ok(());
+        }
+        match query_history(&self.db, user, from, to).await {
+            Ok((base, readings)) => {
+                let buckets = bucket_deltas(base.as_ref(), &readings, granularity);
+                self.send_message(
+                    matcher,
+                    &format!(
+                        "Usage from {} to {}:\n{}",
+                        from,
+                        to,
+                        format_history_page(&buckets, page)
+                    ),
+                )
+                .await?;
+            }
+            Err(e) => {
+                self.send_message(
+                    matcher,
+                    &format!("An error occurred while querying history: {:?}", e),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_export(
+        &self,
+        matcher: &Matcher,
+        format: ExportFormat,
+        with_secrets: bool,
+    ) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            let data = match build_export(&self.db, &config, with_secrets).await {
+                Ok(data) => data,
+                Err(e) => {
+                    self.send_message(
+                        matcher,
+                        &format!("An error occurred while exporting: {:?}", e),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            };
+            let content = match format {
+                ExportFormat::Json => data.to_json()?,
+                ExportFormat::Csv => data.to_csv(),
+            };
+            let file_name = format!(
+                "china_unicom_{}_{}.{}",
+                config.user,
+                data.exported_at.format("%Y%m%d%H%M%S"),
+                format.extension()
+            );
+            if let Err(e) = send_file(matcher, &file_name, content.as_bytes()).await {
+                tracing::info!(
+                    "ChinaUnicom: Send export as file failed, fallback to text: {}",
+                    e
+                );
+                for chunk in chunk_text(&content, TEXT_CHUNK_SIZE) {
+                    self.send_message(matcher, &chunk).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// stop the task of the user and delete all the data
+    async fn remove_user(&self, user: &str) -> std::result::Result<(), sea_orm::DbErr> {
+        if let Some((_user, task)) = self.tasks.remove(user) {
+            task.abort();
+        }
+        self.failures.remove(user);
+        delete_user(&self.db, user).await
+    }
+
+    async fn handle_deregister(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        let _ = matcher
+            .try_send_message(vec![MessageSegment::text(
+                "Are you sure you want to cancel the China Unicom Oxidebot service?\nThis will stop notification service and delete all your data.\nSend 'y' to confirm, 'n' to cancel."
+                    .to_string(),
+            )])
+            .await?;
+        let (easy_bool, matcher) = wait_user_text_generic::<EasyBool>(
+            matcher,
+            &self.broadcast_sender,
+            Duration::from_secs(30),
+            1,
+            None,
+        )
+        .await?;
+
+        // cancel
+        if !easy_bool.0 {
+            return Ok(());
+        }
+
+        match self.remove_user(user).await {
+            Ok(_) => {
+                self.send_message(&matcher, "Deregister success.").await?;
+            }
+            Err(e) => match e {
+                sea_orm::DbErr::RecordNotFound(_) => {
+                    self.send_message(&matcher, "You have not registered yet.")
+                        .await?;
+                }
+                other => {
+                    self.send_message(
+                        &matcher,
+                        &format!(
+                            "An error occurred while deregistering: {}",
+                            other.to_string()
+                        ),
+                    )
+                    .await?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    async fn handle_config_show(&self, matcher: &Matcher, user: &str, json: bool) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            let running = self.tasks.contains_key(user);
+            let text = if json {
+                let mut value = config.to_json(true);
+                value["task_running"] = serde_json::Value::Bool(running);
+                serde_json::to_string_pretty(&value)?
+            } else {
+                format!("{}Task running: {}", config, running)
+            };
+            self.send_message(matcher, &text).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_config_set(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        key: Option<&'static Setting>,
+        value: Option<String>,
+    ) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            let mut matcher = matcher.clone();
+
+            let setting = match key {
+                Some(setting) => setting,
+                None => {
+                    matcher
+                        .try_send_message(vec![MessageSegment::text(settings::menu())])
+                        .await?;
+
+                    let (option, new_matcher) = wait_user_text_generic::<u8>(
+                        &matcher,
+                        &self.broadcast_sender,
+                        Duration::from_secs(30),
+                        3,
+                        Some(format!(
+                            "Please send a number between 0 and {}",
+                            settings::SETTINGS.len()
+                        )),
+                    )
+                    .await?;
+                    matcher = new_matcher;
+
+                    if option == 0 {
+                        self.send_message(&matcher, "Config set operation cancel.")
+                            .await?;
+                        return Ok(());
+                    }
+                    match settings::from_option(option) {
+                        Some(setting) => setting,
+                        None => {
+                            self.send_message(&matcher, "Invalid option number, exited.")
+                                .await?;
+                            return Ok(());
+                        }
+                    }
+                }
+            };
+
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    self.send_message(
+                        &matcher,
+                        &format!(
+                            "Please send the new value of {}({}) in 30s.",
+                            setting.name,
+                            setting.kind.type_hint()
+                        ),
+                    )
+                    .await?;
+                    let (value, new_matcher) = wait_user_text_generic::<String>(
+                        &matcher,
+                        &self.broadcast_sender,
+                        Duration::from_secs(30),
+                        1,
+                        None,
+                    )
+                    .await?;
+                    matcher = new_matcher;
+                    value
+                }
+            };
+
+            let mut config = config;
+            let captured = if setting.name == "cookie" {
+                extract_credentials(&value)
+            } else {
+                None
+            };
+            let normalized = match captured {
+                // a pasted request may update the AppId and TokenOnline too
+                Some(credentials) => {
+                    if credentials.is_empty() {
+                        self.send_message(&matcher, "No credentials found in the pasted request.")
+                            .await?;
+                        return Ok(());
+                    }
+                    let (confirmed, new_matcher) = self
+                        .confirm(
+                            &matcher,
+                            &format!(
+                                "Found in the pasted request:\n{}\nSend 'y' to save, 'n' to cancel.",
+                                credentials.summary()
+                            ),
+                        )
+                        .await?;
+                    matcher = new_matcher;
+                    if !confirmed {
+                        self.send_message(&matcher, "Config set operation cancel.")
+                            .await?;
+                        return Ok(());
+                    }
+                    credentials.apply(&mut config).join(", ")
+                }
+                None => {
+                    let value = if setting.name == "cookie" {
+                        normalize_cookie(&value)
+                    } else {
+                        value
+                    };
+                    match setting.apply(&mut config, &value) {
+                        Ok(normalized) if setting.secret => {
+                            format!("{} = {}", setting.name, mask_secret(&normalized))
+                        }
+                        Ok(normalized) => format!("{} = {}", setting.name, normalized),
+                        Err(e) => {
+                            self.send_message(&matcher, &format!("{}\n{}", e, setting.hint()))
+                                .await?;
+                            return Ok(());
+                        }
+                    }
+                }
+            };
+
+            // the credentials are checked the same as registering before the task uses them
+            if setting.name == "cookie" {
+                if let Err(errors) = config.validate() {
+                    self.send_message(&matcher, &format!("Update failed:\n{}", errors.join("\n")))
+                        .await?;
+                    return Ok(());
+                }
+                self.send_message(&matcher, "Verifying your credentials, please wait...")
+                    .await?;
+                config = match verify_credentials(self.provider.as_ref(), config).await {
+                    Ok((config, _data)) => config,
+                    Err(e) => {
+                        self.send_message(
+                            &matcher,
+                            &format!("Update failed, nothing is saved.\n{}", e),
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                };
+            }
+
+            let config_active: ConfigActiveModel = config.into_active_model().reset_all();
+            match ConfigEntity::update(config_active).exec(&self.db).await {
+                Ok(_) => {
+                    let _ = self
+                        .send_message(&matcher, &format!("Update success: {}", normalized))
+                        .await;
+                    self.handle_restart_task(&matcher, user).await?;
+                }
+                Err(e) => {
+                    self.send_message(
+                        &matcher,
+                        &format!("An error occurred while updating: {:?}", e),
+                    )
+                    .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_task(&self, user: &str) -> Result<()> {
+        self.tasks.insert(
+            user.to_string(),
+            create_china_unicom_task(self.task_context(), user.to_owned()).await?,
+        );
+        Ok(())
+    }
+
+    async fn handle_add_task(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            if !config.enable_task {
+                let mut config_active: ConfigActiveModel = config.into();
+                config_active.enable_task = Set(true);
+                match ConfigEntity::update(config_active).exec(&self.db).await {
+                    Ok(_) => {}
+                    Err(e) => {
+                        self.send_message(
+                            matcher,
+                            &format!("ChinaUnicom: Task start failed to update config: {:?}", e),
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                }
+            }
+            match self.add_task(user).await {
+                Ok(_) => {
+                    self.send_message(matcher, "ChinaUnicom: Task start success.")
+                        .await?;
+                }
+                Err(e) => {
+                    self.send_message(matcher, &format!("ChinaUnicom: Task start failed: {:?}", e))
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_task_stop(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            if config.enable_task {
+                let mut config_active: ConfigActiveModel = config.into();
+                config_active.enable_task = Set(false);
+                match ConfigEntity::update(config_active).exec(&self.db).await {
+                    Ok(_) => {}
+                    Err(e) => {
+                        self.send_message(
+                            matcher,
+                            &format!("ChinaUnicom: Task stop failed to update config: {:?}", e),
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                }
+            }
+            if let Some((_user, task)) = self.tasks.remove(user) {
+                task.abort();
+                self.send_message(matcher, "ChinaUnicom: Task stop success.")
+                    .await?;
+            } else {
+                self.send_message(matcher, "ChinaUnicom: Task is not running.")
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_restart_task(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        if let Some((_user, task)) = self.tasks.remove(user) {
+            task.abort();
+            if let Some(config) = self.get_user_config(matcher).await? {
+                if config.enable_task {
+                    self.handle_add_task(matcher, user).await?;
+                } else {
+                    self.send_message(
+                        matcher,
+                        "ChinaUnicom: Task is stop, so it will not restart.",
+                    )
+                    .await?;
+                }
+            }
+        } else {
+            self.send_message(
+                matcher,
+                "ChinaUnicom: Task is not running, so it will not restart.",
+            )
+            .await?;
+        }
+        Ok(())
+    }
+    async fn handle_task_status(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        let mut message = if self.tasks.contains_key(user) {
+            "ChinaUnicom: Task is running.".to_string()
+        } else {
+            "ChinaUnicom: Task is not running.".to_string()
+        };
+        let config = ConfigEntity::find_by_id(user).one(&self.db).await?;
+        if let Some(until) = config.and_then(|config| config.active_snooze()) {
+            message += &format!(
+                "\nNotifications are snoozed until {}.",
+                until.format("%Y-%m-%d %H:%M")
+            );
+        }
+        self.send_message(matcher, &message).await?;
+        Ok(())
+    }
+
+    async fn handle_task_test(&self, matcher: &Matcher) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            let message = format!(
+                "ChinaUnicom: This is a test notification sent at {}.",
+                Local::now().format("%Y-%m-%d %H:%M:%S")
+            );
+            let notification = Notification::new(&config.user, message);
+            let targets = TargetEntity::find()
+                .filter(TargetColumn::User.eq(config.user.as_str()))
+                .filter(TargetColumn::Enabled.eq(true))
+                .all(&self.db)
+                .await?;
+            if targets.is_empty() {
+                self.send_message(
+                    matcher,
+                    "ChinaUnicom: You have no enabled target, use the `target` command to enable one.",
+                )
+                .await?;
+                return Ok(());
+            }
+            let mut lines = vec!["ChinaUnicom: Test notification results:".to_string()];
+            for target in targets {
+                let result = self
+                    .notifiers
+                    .notify_target(&self.db, &target, &notification)
+                    .await;
+                lines.push(match result {
+                    Ok(_) => format!("{}: sent", target.describe()),
+                    Err(e) => format!("{}: failed, {}", target.describe(), e),
+                });
+            }
+            self.send_message(matcher, &lines.join("\n")).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_task_snooze(&self, matcher: &Matcher, time: &str) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            let until = match parse_snooze(time, Local::now()) {
+                Ok(until) => until,
+                Err(e) => {
+                    self.send_message(matcher, &e).await?;
+                    return Ok(());
+                }
+            };
+            let mut config_active: ConfigActiveModel = config.into();
+            config_active.snooze_until = Set(Some(until));
+            ConfigEntity::update(config_active).exec(&self.db).await?;
+            self.send_message(
+                matcher,
+                &format!(
+                    "ChinaUnicom: Notifications are snoozed until {}, data is still queried and recorded.",
+                    until.format("%Y-%m-%d %H:%M")
+                ),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_task_unsnooze(&self, matcher: &Matcher) -> Result<()> {
+        if let Some(config) = self.get_user_config(matcher).await? {
+            if config.active_snooze().is_none() {
+                self.send_message(matcher, "ChinaUnicom: Notifications are not snoozed.")
+                    .await?;
+                return Ok(());
+            }
+            let mut config_active: ConfigActiveModel = config.into();
+            config_active.snooze_until = Set(None);
+            ConfigEntity::update(config_active).exec(&self.db).await?;
+            self.send_message(matcher, "ChinaUnicom: Notifications are resumed.")
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl EventHandlerTrait for ChinaUnicomHandler {
+    #[must_use]
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn handle<'life0, 'async_trait>(
+        &'life0 self,
+        matcher: Matcher,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            if let Some(message) = matcher.try_get_message() {
+                let raw_text = message.get_raw_text();
+                let Some((prefix, rest)) = self.options.match_prefix(&raw_text) else {
+                    return Ok(());
+                };
+
+                let in_group = matcher.is_group().await;
+                if in_group && !self.options.groups {
+                    self.send_message(&matcher, "This command can only be used in private chat.")
+                        .await?;
+                    return Ok(());
+                }
+
+                let (user, bot) = get_user_bot_from(&matcher)
+                    .await
+                    .ok_or(anyhow::anyhow!("User ot bot not found"))?;
+
+                // the matched prefix is used as the binary name in the help
+                let mut args = vec![prefix.to_string()];
+                args.extend(shlex::split(rest).ok_or(anyhow::anyhow!("Parse shlex error"))?);
+                match Cli::try_parse_with_settings(args) {
+                    Ok(cli) if in_group && !cli.command.allowed_in_group() => {
+                        self.send_message(
+                            &matcher,
+                            "This command can only be used in private chat.",
+                        )
+                        .await?;
+                    }
+                    Ok(cli) => match cli.command {
+                        cli::Commands::Register => {
+                            self.handle_register(&matcher, &user, &bot).await?;
+                        }
+                        cli::Commands::Query { dry_run } => {
+                            self.handle_query(&matcher, dry_run).await?;
+                        }
+                        cli::Commands::Task { task_command } => {
+                            match task_command {
+                                cli::TaskCommand::Start => {
+                                    self.handle_add_task(&matcher, &user).await?;
+                                }
+                                cli::TaskCommand::Status => {
+                                    self.handle_task_status(&matcher, &user).await?;
+                                }
+                                cli::TaskCommand::Test => {
+                                    self.handle_task_test(&matcher).await?;
+                                }
+                                cli::TaskCommand::Stop => {
+                                    self.handle_task_stop(&matcher, &user).await?;
+                                }
+                                cli::TaskCommand::Snooze { time } => {
+                                    self.handle_task_snooze(&matcher, &time.join(" ")).await?;
+                                }
+                                cli::TaskCommand::Unsnooze => {
+                                    self.handle_task_unsnooze(&matcher).await?;
+                                }
+                            };
+                        }
+                        cli::Commands::Config { config_command } => match config_command {
+                            cli::ConfigCommand::Show { json } => {
+                                self.handle_config_show(&matcher, &user, json).await?;
+                            }
+                            cli::ConfigCommand::Set { key, value } => {
+                                self.handle_config_set(&matcher, &user, key, value).await?;
+                            }
+                        },
+                        cli::Commands::History {
+                            from,
+                            to,
+                            granularity,
+                            page,
+                        } => {
+                            self.handle_history(&matcher, &user, from, to, granularity, page)
+                                .await?;
+                        }
+                        cli::Commands::Export {
+                            format,
+                            with_secrets,
+                        } => {
+                            self.handle_export(&matcher, format, with_secrets).await?;
+                        }
+                        cli::Commands::Group { group_command } => {
+                            self.handle_group(&matcher, &user, &bot, group_command)
+                                .await?;
+                        }
+                        cli::Commands::Target { target_command } => {
+                            self.handle_target(&matcher, &user, target_command).await?;
+                        }
+                        #[cfg(feature = "api")]
+                        cli::Commands::Token { token_command } => {
+                            self.handle_token(&matcher, &user, token_command).await?;
+                        }
+                        cli::Commands::Admin { admin_command } => {
+                            if !self.options.is_admin(&user) {
+                                self.send_message(&matcher, "This command is only for admins.")
+                                    .await?;
+                                return Ok(());
+                            }
+                            self.handle_admin(&matcher, admin_command).await?;
+                        }
+                        cli::Commands::Deregister => {
+                            self.handle_deregister(&matcher, &user).await?;
+                        }
+                    },
+                    Err(e) => {
+                        self.send_message(&matcher, &e.to_string()).await?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
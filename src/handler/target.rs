@@ -0,0 +1,231 @@
+use anyhow::Result;
+use oxidebot::matcher::Matcher;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, Set,
+};
+
+use crate::{
+    cli::TargetCommand,
+    model::{target::Column, TargetEntity, TargetModel},
+    ChinaUnicomHandler,
+};
+
+impl ChinaUnicomHandler {
+    pub(crate) async fn handle_target(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        command: TargetCommand,
+    ) -> Result<()> {
+        if self.get_user_config(matcher).await?.is_none() {
+            return Ok(());
+        }
+        match command {
+            TargetCommand::List => self.handle_target_list(matcher, user).await,
+            #[cfg(feature = "webhook")]
+            TargetCommand::Webhook {
+                url,
+                secret,
+                retries,
+            } => {
+                let options = crate::notifier::webhook::WebhookOptions {
+                    secret,
+                    retries,
+                    allow_private: false,
+                };
+                self.handle_add_webhook(matcher, user, &url, &options).await
+            }
+            #[cfg(feature = "email")]
+            TargetCommand::Email { address } => {
+                self.handle_add_email(matcher, user, &address).await
+            }
+            TargetCommand::Enable { id } => {
+                self.handle_target_enabled(matcher, user, id, true).await
+            }
+            TargetCommand::Disable { id } => {
+                self.handle_target_enabled(matcher, user, id, false).await
+            }
+            TargetCommand::Remove { id } => self.handle_target_remove(matcher, user, id).await,
+        }
+    }
+
+    /// get the target of the user, if not found, send message to user
+    async fn user_target(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        id: i32,
+    ) -> Result<Option<TargetModel>> {
+        let target = TargetEntity::find_by_id(id)
+            .filter(Column::User.eq(user))
+            .one(&self.db)
+            .await?;
+        if target.is_none() {
+            self.send_message(
+                matcher,
+                &format!(
+                    "Target #{} not found, use `target list` to see your targets.",
+                    id
+                ),
+            )
+            .await?;
+        }
+        Ok(target)
+    }
+
+    /// add a webhook target of the user, shared by the user and admin commands
+    #[cfg(feature = "webhook")]
+    pub(crate) async fn handle_add_webhook(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        url: &str,
+        options: &crate::notifier::webhook::WebhookOptions,
+    ) -> Result<()> {
+        let result =
+            crate::notifier::webhook::add_webhook_target(&self.db, user, url, options).await;
+        self.report_added_target(matcher, result).await
+    }
+
+    /// add an email target of the user after the code sent to the address is echoed back,
+    /// so nobody can send the notifications to an address they don't own
+    #[cfg(feature = "email")]
+    async fn handle_add_email(&self, matcher: &Matcher, user: &str, address: &str) -> Result<()> {
+        let Some(smtp) = &self.options.smtp else {
+            self.send_message(matcher, "Email is not enabled on this bot.")
+                .await?;
+            return Ok(());
+        };
+        let address = match crate::notifier::email::parse_address(address) {
+            Ok(address) => address,
+            Err(e) => {
+                self.send_message(matcher, &e.to_string()).await?;
+                return Ok(());
+            }
+        };
+        let code = crate::notifier::email::confirmation_code();
+        if let Err(e) = crate::notifier::EmailNotifier::new(smtp)?
+            .send_code(&address, &code)
+            .await
+        {
+            self.send_message(
+                matcher,
+                &format!("Failed to send the confirmation code: {}", e),
+            )
+            .await?;
+            return Ok(());
+        }
+        self.send_message(
+            matcher,
+            &format!(
+                "A confirmation code is sent to {}, please send it here in 5 minutes.",
+                address
+            ),
+        )
+        .await?;
+        let (text, matcher) = oxidebot::wait_user_text_generic::<String>(
+            matcher,
+            &self.broadcast_sender,
+            std::time::Duration::from_secs(300),
+            1,
+            None,
+        )
+        .await?;
+        if text.trim() != code {
+            self.send_message(
+                &matcher,
+                "The confirmation code is wrong, the email target is not added.",
+            )
+            .await?;
+            return Ok(());
+        }
+        let result =
+            crate::notifier::email::add_email_target(&self.db, user, address.as_ref()).await;
+        self.report_added_target(&matcher, result).await
+    }
+
+    /// send the result of adding a target to user
+    #[cfg(any(feature = "webhook", feature = "email"))]
+    async fn report_added_target(
+        &self,
+        matcher: &Matcher,
+        result: Result<TargetModel>,
+    ) -> Result<()> {
+        match result {
+            Ok(target) => {
+                self.send_message(matcher, &format!("Target added: {}", target.describe()))
+                    .await?;
+            }
+            Err(e)
+                if e.downcast_ref::<sea_orm::DbErr>()
+                    .and_then(|e| e.sql_err())
+                    .is_some_and(|e| {
+                        matches!(e, sea_orm::SqlErr::UniqueConstraintViolation(_))
+                    }) =>
+            {
+                self.send_message(matcher, "It is already a target.")
+                    .await?;
+            }
+            Err(e) => {
+                self.send_message(
+                    matcher,
+                    &format!("An error occurred while adding the target: {}", e),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_target_list(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        let targets = TargetEntity::find()
+            .filter(Column::User.eq(user))
+            .order_by_asc(Column::Id)
+            .all(&self.db)
+            .await?;
+        if targets.is_empty() {
+            self.send_message(
+                matcher,
+                "You have no target, notifications are not delivered.",
+            )
+            .await?;
+            return Ok(());
+        }
+        let lines: Vec<String> = targets.iter().map(|target| target.describe()).collect();
+        self.send_message(matcher, &format!("Targets:\n{}", lines.join("\n")))
+            .await?;
+        Ok(())
+    }
+
+    async fn handle_target_enabled(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        id: i32,
+        enabled: bool,
+    ) -> Result<()> {
+        let Some(target) = self.user_target(matcher, user, id).await? else {
+            return Ok(());
+        };
+        let mut target_active = target.into_active_model();
+        target_active.enabled = Set(enabled);
+        // give the target a fresh start after enabled again
+        if enabled {
+            target_active.failures = Set(0);
+        }
+        let target = target_active.update(&self.db).await?;
+        self.send_message(matcher, &format!("Target updated: {}", target.describe()))
+            .await?;
+        Ok(())
+    }
+
+    async fn handle_target_remove(&self, matcher: &Matcher, user: &str, id: i32) -> Result<()> {
+        let Some(target) = self.user_target(matcher, user, id).await? else {
+            return Ok(());
+        };
+        TargetEntity::delete_by_id(target.id).exec(&self.db).await?;
+        self.send_message(matcher, &format!("Target removed: {}", target.describe()))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use oxidebot::matcher::Matcher;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+use crate::{
+    api::create_token,
+    cli::TokenCommand,
+    model::{api_token::Column, ApiTokenEntity},
+    ChinaUnicomHandler,
+};
+
+impl ChinaUnicomHandler {
+    pub(crate) async fn handle_token(
+        &self,
+        matcher: &Matcher,
+        user: &str,
+        command: TokenCommand,
+    ) -> Result<()> {
+        if self.get_user_config(matcher).await?.is_none() {
+            return Ok(());
+        }
+        match command {
+            TokenCommand::Create { name } => self.handle_token_create(matcher, user, &name).await,
+            TokenCommand::List => self.handle_token_list(matcher, user).await,
+            TokenCommand::Revoke { id } => self.handle_token_revoke(matcher, user, id).await,
+        }
+    }
+
+    async fn handle_token_create(&self, matcher: &Matcher, user: &str, name: &str) -> Result<()> {
+        if self.options.api.is_none() {
+            self.send_message(matcher, "The http api is not enabled on this bot.")
+                .await?;
+            return Ok(());
+        }
+        let (model, token) = create_token(&self.db, user, name).await?;
+        self.send_message(
+            matcher,
+            &format!(
+                "Token #{} created, it is shown only this time:\n{}\nSend it in the header `Authorization: Bearer <token>`, use `token revoke {}` if it is leaked.",
+                model.id, token, model.id
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn handle_token_list(&self, matcher: &Matcher, user: &str) -> Result<()> {
+        let tokens = ApiTokenEntity::find()
+            .filter(Column::User.eq(user))
+            .order_by_asc(Column::Id)
+            .all(&self.db)
+            .await?;
+        if tokens.is_empty() {
+            self.send_message(matcher, "You have no token.").await?;
+            return Ok(());
+        }
+        let lines: Vec<String> = tokens.iter().map(|token| token.describe()).collect();
+        self.send_message(matcher, &format!("Tokens:\n{}", lines.join("\n")))
+            .await?;
+        Ok(())
+    }
+
+    async fn handle_token_revoke(&self, matcher: &Matcher, user: &str, id: i32) -> Result<()> {
+        let result = ApiTokenEntity::delete_many()
+            .filter(Column::Id.eq(id))
+            .filter(Column::User.eq(user))
+            .exec(&self.db)
+            .await?;
+        let message = if result.rows_affected == 0 {
+            format!(
+                "Token #{} not found, use `token list` to see your tokens.",
+                id
+            )
+        } else {
+            format!("Token #{} revoked.", id)
+        };
+        self.send_message(matcher, &message).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+//! prometheus metrics and the http endpoint to scrape them, only with the `metrics` feature
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, LazyLock},
+    time::Instant,
+};
+
+use async_trait::async_trait;
+use axum::{extract::State, routing::get, Router};
+use china_unicom_rs::data::ChinaUnicomData;
+use dashmap::DashMap;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use tokio::task::JoinHandle;
+
+use crate::{
+    model::target::TargetKind,
+    notifier::UsageEvent,
+    provider::{ErrorKind, RefreshedCredentials, SharedProvider, UsageProvider},
+    publisher::UsagePublisher,
+};
+
+type Tasks = Arc<DashMap<String, JoinHandle<()>>>;
+
+/// the address of the endpoint, and whether to export the usage of each user
+#[derive(Clone, Debug)]
+pub struct MetricsOptions {
+    pub addr: SocketAddr,
+    /// export the usage gauges labeled by user, off by default as the users are exposed
+    pub per_user: bool,
+}
+
+impl MetricsOptions {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            per_user: false,
+        }
+    }
+
+    pub fn per_user(mut self) -> Self {
+        self.per_user = true;
+        self
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    pub queries_attempted: IntCounter,
+    pub queries_succeeded: IntCounter,
+    /// labeled by `class`, `auth_expired` or `other`
+    pub queries_failed: IntCounterVec,
+    /// labeled by `call`, `fetch_usage` or `refresh_credentials`
+    pub upstream_seconds: HistogramVec,
+    /// labeled by `result`, `success` or `failure`
+    pub auth_refreshes: IntCounterVec,
+    /// labeled by the `kind` of the target and `result`
+    pub notifications: IntCounterVec,
+    pub running_tasks: IntGauge,
+    /// labeled by `user` and `kind`, only with `per_user`
+    pub flow_used: GaugeVec,
+    /// labeled by `user` and `kind`, only with `per_user`
+    pub flow_left: GaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("china_unicom".to_string()), None)
+            .expect("the prefix is valid");
+        let metrics = Self {
+            queries_attempted: IntCounter::new("queries_attempted_total", "Queries attempted")
+                .unwrap(),
+            queries_succeeded: IntCounter::new("queries_succeeded_total", "Queries succeeded")
+                .unwrap(),
+            queries_failed: IntCounterVec::new(
+                Opts::new("queries_failed_total", "Queries failed by error class"),
+                &["class"],
+            )
+            .unwrap(),
+            upstream_seconds: HistogramVec::new(
+                HistogramOpts::new("upstream_seconds", "Latency of the upstream calls"),
+                &["call"],
+            )
+            .unwrap(),
+            auth_refreshes: IntCounterVec::new(
+                Opts::new("auth_refreshes_total", "Refreshes of the expired cookies"),
+                &["result"],
+            )
+            .unwrap(),
+            notifications: IntCounterVec::new(
+                Opts::new("notifications_total", "Notifications delivered to targets"),
+                &["kind", "result"],
+            )
+            .unwrap(),
+            running_tasks: IntGauge::new("running_tasks", "Tasks running").unwrap(),
+            flow_used: GaugeVec::new(
+                Opts::new("flow_used_gb", "Flow used of the user"),
+                &["user", "kind"],
+            )
+            .unwrap(),
+            flow_left: GaugeVec::new(
+                Opts::new("flow_left_gb", "Flow left of the user"),
+                &["user", "kind"],
+            )
+            .unwrap(),
+            registry,
+        };
+        let registry = &metrics.registry;
+        registry
+            .register(Box::new(metrics.queries_attempted.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.queries_succeeded.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.queries_failed.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.upstream_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.auth_refreshes.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.notifications.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.running_tasks.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.flow_used.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.flow_left.clone()))
+            .unwrap();
+        metrics
+    }
+
+    /// the text exposition of all the metrics
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("the metrics are valid");
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// count the result of a query, the errors are classified by the provider
+pub fn record_query<T>(provider: &dyn UsageProvider, result: &anyhow::Result<T>) {
+    match result {
+        Ok(_) => METRICS.queries_succeeded.inc(),
+        Err(e) => {
+            let class = match provider.classify_error(e) {
+                ErrorKind::AuthExpired => "auth_expired",
+                ErrorKind::Other => "other",
+            };
+            METRICS.queries_failed.with_label_values(&[class]).inc();
+        }
+    }
+}
+
+pub fn record_notification(kind: TargetKind, success: bool) {
+    METRICS
+        .notifications
+        .with_label_values(&[
+            &kind.to_string(),
+            if success { "success" } else { "failure" },
+        ])
+        .inc();
+}
+
+/// a provider recording the latency and the refreshes of another
+pub struct MeteredProvider(pub SharedProvider);
+
+#[async_trait]
+impl UsageProvider for MeteredProvider {
+    async fn fetch_usage(&self, cookie: &str) -> anyhow::Result<ChinaUnicomData> {
+        let start = Instant::now();
+        let result = self.0.fetch_usage(cookie).await;
+        METRICS
+            .upstream_seconds
+            .with_label_values(&["fetch_usage"])
+            .observe(start.elapsed().as_secs_f64());
+        result
+    }
+
+    async fn refresh_credentials(
+        &self,
+        token_online: &str,
+        app_id: &str,
+    ) -> anyhow::Result<RefreshedCredentials> {
+        let start = Instant::now();
+        let result = self.0.refresh_credentials(token_online, app_id).await;
+        METRICS
+            .upstream_seconds
+            .with_label_values(&["refresh_credentials"])
+            .observe(start.elapsed().as_secs_f64());
+        METRICS
+            .auth_refreshes
+            .with_label_values(&[if result.is_ok() { "success" } else { "failure" }])
+            .inc();
+        result
+    }
+
+    fn classify_error(&self, error: &anyhow::Error) -> ErrorKind {
+        self.0.classify_error(error)
+    }
+}
+
+/// set the usage gauges of the user at every query
+pub struct MetricsPublisher;
+
+#[async_trait]
+impl UsagePublisher for MetricsPublisher {
+    async fn publish(&self, user: &str, event: &UsageEvent) -> anyhow::Result<()> {
+        let data = &event.data;
+        for (kind, value) in [
+            ("sum", data.sum_flow_used),
+            ("general", data.non_limit_flow_used),
+            ("directed", data.limit_flow_used),
+            ("free", data.free_flow_used),
+            ("non_free", data.non_free_flow_used),
+        ] {
+            METRICS
+                .flow_used
+                .with_label_values(&[user, kind])
+                .set(value);
+        }
+        for (kind, value) in [
+            ("general", data.non_limit_flow - data.non_limit_flow_used),
+            ("directed", data.limit_flow - data.limit_flow_used),
+        ] {
+            METRICS
+                .flow_left
+                .with_label_values(&[user, kind])
+                .set(value);
+        }
+        Ok(())
+    }
+}
+
+async fn metrics_handler(State(tasks): State<Tasks>) -> String {
+    let running = tasks.iter().filter(|task| !task.is_finished()).count();
+    METRICS.running_tasks.set(running as i64);
+    METRICS.render()
+}
+
+/// serve `GET /metrics` in the background
+pub fn serve(options: &MetricsOptions, tasks: Tasks) {
+    let addr = options.addr;
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(tasks);
+    tokio::spawn(async move {
+        let result = async {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            axum::serve(listener, app).await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::error!("ChinaUnicom: Metrics endpoint on {} failed: {}", addr, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MeteredProvider, MetricsPublisher, METRICS};
+    use crate::{
+        notifier::UsageEvent,
+        publisher::UsagePublisher,
+        testing::{at, fake_context, insert_config, usage},
+        utils::china_unicom::query_once,
+    };
+
+    #[tokio::test]
+    async fn record() {
+        let (context, provider, _sink) = fake_context().await;
+        let config = insert_config(&context.db, "metrics_user").await;
+        let metered = MeteredProvider(provider.clone());
+        provider
+            .push_auth_expired()
+            .push_usage(usage(at(1, 8, 0), 1.0))
+            .push_error("network error");
+        let attempted = METRICS.queries_attempted.get();
+
+        query_once(&context.db, &metered, &context.publishers, &config)
+            .await
+            .unwrap();
+        assert!(
+            query_once(&context.db, &metered, &context.publishers, &config)
+                .await
+                .is_err()
+        );
+        assert!(METRICS.queries_attempted.get() >= attempted + 2);
+
+        MetricsPublisher
+            .publish(
+                "metrics_user",
+                &UsageEvent {
+                    data: usage(at(1, 8, 0), 1.0),
+                    last: None,
+                    daily: None,
+                    fired: Vec::new(),
+                },
+            )
+            .await
+            .unwrap();
+        let text = METRICS.render();
+        assert!(text.contains("china_unicom_queries_failed_total{class=\"other\"}"));
+        assert!(text.contains("china_unicom_auth_refreshes_total{result=\"success\"}"));
+        assert!(text.contains("china_unicom_upstream_seconds_bucket{call=\"fetch_usage\""));
+        assert!(
+            text.contains("china_unicom_flow_used_gb{kind=\"non_free\",user=\"metrics_user\"} 1")
+        );
+    }
+}
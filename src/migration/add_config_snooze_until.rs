@@ -0,0 +1,32 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use super::create_config_table::ConfigTable;
+use crate::model::config::Column;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ConfigTable::Table)
+                    .add_column(timestamp_with_time_zone_null(Column::SnoozeUntil))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ConfigTable::Table)
+                    .drop_column(Column::SnoozeUntil)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,37 @@
+use crate::model::api_token::Column;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiTokenTable::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Column::Id))
+                    .col(string(Column::User))
+                    .col(string(Column::Name))
+                    .col(string_uniq(Column::TokenHash))
+                    .col(timestamp_with_time_zone(Column::Created))
+                    .col(timestamp_with_time_zone_null(Column::LastUsed))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiTokenTable::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum ApiTokenTable {
+    #[sea_orm(iden = "api_token")]
+    Table,
+}
@@ -0,0 +1,50 @@
+use crate::model::history::Column;
+use sea_orm_migration::{prelude::*, schema::*};
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(HistoryTable::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Column::Id))
+                    .col(string(Column::User))
+                    .col(timestamp_with_time_zone(Column::Time))
+                    .col(float(Column::SumFlowUsed))
+                    .col(float(Column::LimitFlowUsed))
+                    .col(float(Column::NonLimitFlowUsed))
+                    .col(float(Column::FreeFlowUsed))
+                    .col(float(Column::NonFreeFlowUsed))
+                    .col(integer(Column::SumVoiceUsed))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_history_user_time")
+                    .table(HistoryTable::Table)
+                    .col(Column::User)
+                    .col(Column::Time)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(HistoryTable::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum HistoryTable {
+    #[sea_orm(iden = "history")]
+    Table,
+}
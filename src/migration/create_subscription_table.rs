@@ -0,0 +1,50 @@
+use sea_orm_migration::{prelude::*, schema::*};
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SubscriptionTable::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SubscriptionTable::Id))
+                    .col(string(SubscriptionTable::User))
+                    .col(string(SubscriptionTable::Bot))
+                    .col(string(SubscriptionTable::Group))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_subscription_user_group")
+                    .table(SubscriptionTable::Table)
+                    .col(SubscriptionTable::User)
+                    .col(SubscriptionTable::Group)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SubscriptionTable::Table).to_owned())
+            .await
+    }
+}
+
+/// the subscriptions are moved to the target table by a later migration
+#[derive(DeriveIden)]
+pub enum SubscriptionTable {
+    #[sea_orm(iden = "subscription")]
+    Table,
+    Id,
+    User,
+    Bot,
+    Group,
+}
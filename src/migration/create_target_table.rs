@@ -0,0 +1,88 @@
+use crate::model::target::Column;
+use sea_orm_migration::{prelude::*, schema::*};
+
+use super::create_subscription_table::SubscriptionTable;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TargetTable::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Column::Id))
+                    .col(string(Column::User))
+                    .col(string(Column::Kind))
+                    .col(string(Column::Address))
+                    .col(string(Column::Bot))
+                    .col(string(Column::Options).default("{}"))
+                    .col(boolean(Column::Enabled).default(true))
+                    .col(integer(Column::Failures).default(0))
+                    .col(string_null(Column::LastError))
+                    .col(timestamp_with_time_zone_null(Column::LastFailure))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_target_user_kind_address")
+                    .table(TargetTable::Table)
+                    .col(Column::User)
+                    .col(Column::Kind)
+                    .col(Column::Address)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // the private chat of every registered user, and the subscribed groups
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "INSERT INTO target (user, kind, address, bot) SELECT user, 'private', user, bot FROM config",
+        )
+        .await?;
+        db.execute_unprepared(
+            r#"INSERT INTO target (user, kind, address, bot) SELECT user, 'group', "group", bot FROM subscription"#,
+        )
+        .await?;
+        manager
+            .drop_table(Table::drop().table(SubscriptionTable::Table).to_owned())
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SubscriptionTable::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SubscriptionTable::Id))
+                    .col(string(SubscriptionTable::User))
+                    .col(string(SubscriptionTable::Bot))
+                    .col(string(SubscriptionTable::Group))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"INSERT INTO subscription (user, bot, "group") SELECT user, bot, address FROM target WHERE kind = 'group'"#,
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TargetTable::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TargetTable {
+    #[sea_orm(iden = "target")]
+    Table,
+}
@@ -3,6 +3,11 @@ use sea_orm_migration::{async_trait, MigrationTrait, MigratorTrait};
 mod create_config_table;
 mod create_last_table;
 mod create_daily_table;
+mod create_history_table;
+mod create_subscription_table;
+mod add_config_snooze_until;
+mod create_target_table;
+mod create_api_token_table;
 pub struct Migrator;
 
 #[async_trait::async_trait]
@@ -12,6 +17,11 @@ impl MigratorTrait for Migrator {
             Box::new(create_config_table::Migration),
             Box::new(create_last_table::Migration),
             Box::new(create_daily_table::Migration),
+            Box::new(create_history_table::Migration),
+            Box::new(create_subscription_table::Migration),
+            Box::new(add_config_snooze_until::Migration),
+            Box::new(create_target_table::Migration),
+            Box::new(create_api_token_table::Migration),
         ]
     }
 }
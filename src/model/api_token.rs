@@ -0,0 +1,73 @@
+use chrono::{DateTime, Local};
+use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+
+/// a token of a user to read the usage by the http api, only the hash is stored
+#[derive(Clone, Debug, DeriveEntityModel)]
+#[sea_orm(table_name = "api_token")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+
+    pub user: String,
+    // 用户起的名字, 用于区分不同的 token
+    pub name: String,
+    // token 的 sha256
+    #[sea_orm(unique)]
+    pub token_hash: String,
+    pub created: DateTime<Local>,
+    pub last_used: Option<DateTime<Local>>,
+}
+
+impl Model {
+    pub fn describe(&self) -> String {
+        format!(
+            "#{} {}, created {}, {}",
+            self.id,
+            self.name,
+            self.created.format("%Y-%m-%d %H:%M"),
+            match self.last_used {
+                Some(time) => format!("last used {}", time.format("%Y-%m-%d %H:%M")),
+                None => "never used".to_string(),
+            }
+        )
+    }
+}
+
+pub fn build_api_token_active(
+    user: String,
+    name: String,
+    token_hash: String,
+) -> super::ApiTokenActiveModel {
+    super::ApiTokenActiveModel {
+        id: NotSet,
+        user: Set(user),
+        name: Set(name),
+        token_hash: Set(token_hash),
+        created: Set(Local::now()),
+        last_used: Set(None),
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Config,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Relation::Config => Entity::belongs_to(super::config::Entity)
+                .from(Column::User)
+                .to(super::config::Column::User)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::config::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Config.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
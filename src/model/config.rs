@@ -1,6 +1,11 @@
 use std::fmt::Display;
 
+use chrono::{DateTime, Local};
 use sea_orm::entity::prelude::*;
+use serde_json::json;
+
+use super::settings::{SettingKind, SettingValue, SETTINGS};
+use crate::utils::redact::{is_masked, mask_secret};
 
 #[derive(Clone, Debug, DeriveEntityModel)]
 #[sea_orm(table_name = "config")]
@@ -13,36 +18,33 @@ pub struct Model {
     pub app_id: String,
     // 是否启用定时任务
     pub enable_task: bool,
-    // 查询间隔(s, min = 60)
+    // 查询间隔(s, 60 <= interval <= 86400)
     pub interval: i64,
-    // 超时时间(s, min = 60)
+    // 超时时间(s, 60 <= timeout <= 2592000)
     pub timeout: Option<i64>,
     // 免费流量阈值(GB)
     pub free_threshold: Option<f64>,
     // 非免费流量阈值(GB)
     pub nonfree_threshold: Option<f64>,
+    // 暂停通知直到该时间, 期间仍会查询和记录
+    pub snooze_until: Option<DateTime<Local>>,
 }
 
 impl Display for Model {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // 带有注释，方便查看
-        writeln!(f, "Cookie: {}", self.cookie)?;
-        writeln!(f, "Interval: {}s", self.interval)?;
-        if let Some(timeout) = self.timeout {
-            writeln!(f, "Timeout: {}s", timeout)?;
-        } else {
-            writeln!(f, "Timeout: None")?;
-        }
-        if let Some(free_threshold) = self.free_threshold {
-            writeln!(f, "Free threshold: {:.2} GB", free_threshold)?;
-        } else {
-            writeln!(f, "Free threshold: None")?;
+        // secrets are masked, so it is safe to send to chat
+        writeln!(f, "User: {}", self.user)?;
+        writeln!(f, "Bot: {}", self.bot)?;
+        writeln!(f, "AppId: {}", mask_secret(&self.app_id))?;
+        writeln!(f, "TokenOnline: {}", mask_secret(&self.token_online))?;
+        for setting in SETTINGS.iter() {
+            writeln!(f, "{}: {}", setting.label, setting.display_redacted(self))?;
         }
-        if let Some(nonfree_threshold) = self.nonfree_threshold {
-            writeln!(f, "Nonfree threshold: {:.2} GB", nonfree_threshold)
-        } else {
-            writeln!(f, "Nonfree threshold: None")
+        writeln!(f, "Task enabled: {}", self.enable_task)?;
+        if let Some(until) = self.active_snooze() {
+            writeln!(f, "Snoozed until: {}", until.format("%Y-%m-%d %H:%M"))?;
         }
+        Ok(())
     }
 }
 
@@ -59,7 +61,155 @@ impl Default for Model {
             nonfree_threshold: Some(0.05),
             token_online: String::with_capacity(0),
             app_id: String::with_capacity(0),
+            snooze_until: None,
+        }
+    }
+}
+
+pub const MIN_INTERVAL: i64 = 60;
+pub const MAX_INTERVAL: i64 = 86400;
+pub const MIN_TIMEOUT: i64 = 60;
+pub const MAX_TIMEOUT: i64 = 30 * 86400;
+// 阈值上限(GB)
+pub const MAX_THRESHOLD: f64 = 1024.0;
+
+impl Model {
+    /// check every field against its bounds, return all problems found
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.app_id.trim().is_empty() {
+            errors.push("AppId can not be empty.".to_string());
+        }
+        if self.token_online.trim().is_empty() {
+            errors.push("TokenOnline can not be empty.".to_string());
+        }
+        for setting in SETTINGS.iter() {
+            if let Err(e) = setting.check(self) {
+                errors.push(e);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// the json form of the config, secrets are masked if `redact` is true
+    pub fn to_json(&self, redact: bool) -> serde_json::Value {
+        let mask = |secret: &str| {
+            if redact {
+                mask_secret(secret)
+            } else {
+                secret.to_string()
+            }
+        };
+        let mut settings = serde_json::Map::new();
+        for setting in SETTINGS.iter() {
+            let value = if redact {
+                setting.json_redacted(self)
+            } else {
+                (setting.get)(self).to_json()
+            };
+            settings.insert(setting.name.to_string(), value);
+        }
+        json!({
+            "user": self.user,
+            "bot": self.bot,
+            "app_id": mask(&self.app_id),
+            "token_online": mask(&self.token_online),
+            "settings": settings,
+            "enable_task": self.enable_task,
+            "snooze_until": self.snooze_until,
+        })
+    }
+
+    /// build the config from the json form of [`Model::to_json`].
+    /// Missing or masked values are taken from `existing`, the result is validated
+    pub fn from_json(
+        value: &serde_json::Value,
+        existing: Option<&Model>,
+    ) -> Result<Self, Vec<String>> {
+        let user = value
+            .get("user")
+            .and_then(|user| user.as_str())
+            .ok_or(vec!["Missing user in config.".to_string()])?;
+        let mut config = existing.cloned().unwrap_or_else(|| Model {
+            user: user.to_string(),
+            ..Default::default()
+        });
+        let mut errors = Vec::new();
+
+        let mut secret =
+            |name: &str, current: &mut String| match value.get(name).and_then(|v| v.as_str()) {
+                Some(secret) if !is_masked(secret) => *current = secret.to_string(),
+                Some(_) if existing.is_none() => errors.push(format!(
+                    "{} of {} is masked, please export with `--with-secrets`.",
+                    name, user
+                )),
+                _ => {}
+            };
+        secret("app_id", &mut config.app_id);
+        secret("token_online", &mut config.token_online);
+
+        if let Some(bot) = value.get("bot").and_then(|v| v.as_str()) {
+            config.bot = bot.to_string();
         }
+        if let Some(enable_task) = value.get("enable_task").and_then(|v| v.as_bool()) {
+            config.enable_task = enable_task;
+        }
+        if let Some(snooze_until) = value.get("snooze_until") {
+            match serde_json::from_value(snooze_until.clone()) {
+                Ok(snooze_until) => config.snooze_until = snooze_until,
+                Err(_) => errors.push(format!("Invalid value of snooze_until: {}", snooze_until)),
+            }
+        }
+        for setting in SETTINGS.iter() {
+            let Some(json) = value.get("settings").and_then(|s| s.get(setting.name)) else {
+                continue;
+            };
+            if setting.secret && json.as_str().is_some_and(is_masked) {
+                if existing.is_none() {
+                    errors.push(format!(
+                        "{} of {} is masked, please export with `--with-secrets`.",
+                        setting.name, user
+                    ));
+                }
+                continue;
+            }
+            match SettingValue::from_json(setting.kind, json) {
+                Some(setting_value) => (setting.set)(&mut config, setting_value),
+                None => errors.push(format!("Invalid value of {}: {}", setting.name, json)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// the snooze time if notifications are snoozed now
+    pub fn active_snooze(&self) -> Option<DateTime<Local>> {
+        self.snooze_until.filter(|until| *until > Local::now())
+    }
+
+    /// reset the out of bounds settings to their default value,
+    /// return the names of the repaired fields
+    pub fn repair(&mut self) -> Vec<&'static str> {
+        let default = Self::default();
+        let mut repaired = Vec::new();
+        for setting in SETTINGS.iter() {
+            if matches!(setting.kind, SettingKind::Text) {
+                continue;
+            }
+            if setting.check(self).is_err() {
+                (setting.set)(self, (setting.get)(&default));
+                repaired.push(setting.name);
+            }
+        }
+        repaired
     }
 }
 
@@ -69,6 +219,12 @@ pub enum Relation {
     Today,
     #[sea_orm(has_one = "super::daily::Entity")]
     Yesterday,
+    #[sea_orm(has_many = "super::history::Entity")]
+    History,
+    #[sea_orm(has_many = "super::target::Entity")]
+    Target,
+    #[sea_orm(has_many = "super::api_token::Entity")]
+    ApiToken,
 }
 
 impl Related<super::last::Entity> for Entity {
@@ -83,4 +239,22 @@ impl Related<super::daily::Entity> for Entity {
     }
 }
 
+impl Related<super::history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::History.def()
+    }
+}
+
+impl Related<super::target::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Target.def()
+    }
+}
+
+impl Related<super::api_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiToken.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,8 +1,9 @@
 use china_unicom_rs::data::ChinaUnicomData;
 use chrono::{DateTime, Local};
 use sea_orm::{entity::prelude::*, Set, Unchanged};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Default, DeriveEntityModel)]
+#[derive(Clone, Debug, Default, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "daily")]
 pub struct Model {
     #[sea_orm(primary_key)]
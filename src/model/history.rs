@@ -0,0 +1,81 @@
+use china_unicom_rs::data::ChinaUnicomData;
+use chrono::{DateTime, Local};
+use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+use serde::{Deserialize, Serialize};
+
+/// a reading of the used data, recorded on every query
+#[derive(Clone, Debug, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "history")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+
+    pub user: String,
+
+    // 查询时间
+    pub time: DateTime<Local>,
+
+    // 已用流量
+    pub sum_flow_used: f64,
+    // 已用定向流量
+    pub limit_flow_used: f64,
+    // 已用通用流量
+    pub non_limit_flow_used: f64,
+    // 已用免费流量
+    pub free_flow_used: f64,
+    // 已用非免费流量
+    pub non_free_flow_used: f64,
+
+    // 已用通话
+    pub sum_voice_used: i64,
+}
+
+impl Model {
+    /// whether the used data is the same as the new data
+    pub fn same_usage(&self, data: &ChinaUnicomData) -> bool {
+        self.sum_flow_used == data.sum_flow_used
+            && self.limit_flow_used == data.limit_flow_used
+            && self.non_limit_flow_used == data.non_limit_flow_used
+            && self.free_flow_used == data.free_flow_used
+            && self.non_free_flow_used == data.non_free_flow_used
+            && self.sum_voice_used == data.sum_voice_used
+    }
+}
+
+pub fn build_history_active(data: &ChinaUnicomData, user: String) -> super::HistoryActiveModel {
+    super::HistoryActiveModel {
+        id: NotSet,
+        user: Set(user),
+        time: Set(data.time),
+        sum_flow_used: Set(data.sum_flow_used),
+        limit_flow_used: Set(data.limit_flow_used),
+        non_limit_flow_used: Set(data.non_limit_flow_used),
+        free_flow_used: Set(data.free_flow_used),
+        non_free_flow_used: Set(data.non_free_flow_used),
+        sum_voice_used: Set(data.sum_voice_used),
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Config,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Relation::Config => Entity::belongs_to(super::config::Entity)
+                .from(Column::User)
+                .to(super::config::Column::User)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::config::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Config.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,10 +1,11 @@
 use china_unicom_rs::data::ChinaUnicomData;
 use chrono::{DateTime, Local};
 use sea_orm::{entity::prelude::*, Set, Unchanged};
+use serde::{Deserialize, Serialize};
 
 use super::DailyModel;
 
-#[derive(Clone, Debug, Default, DeriveEntityModel)]
+#[derive(Clone, Debug, Default, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "last")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -10,6 +10,19 @@ pub mod daily;
 pub use daily::ActiveModel as DailyActiveModel;
 pub use daily::Entity as DailyEntity;
 pub use daily::Model as DailyModel;
+pub mod history;
+pub use history::ActiveModel as HistoryActiveModel;
+pub use history::Entity as HistoryEntity;
+pub use history::Model as HistoryModel;
+pub mod settings;
+pub mod target;
+pub use target::ActiveModel as TargetActiveModel;
+pub use target::Entity as TargetEntity;
+pub use target::Model as TargetModel;
+pub mod api_token;
+pub use api_token::ActiveModel as ApiTokenActiveModel;
+pub use api_token::Entity as ApiTokenEntity;
+pub use api_token::Model as ApiTokenModel;
 
 #[cfg(test)]
 pub mod data_test {
@@ -18,23 +31,90 @@ pub mod data_test {
         ConfigActiveModel, ConfigEntity, ConfigModel, DailyActiveModel, DailyEntity, DailyModel,
         LastActiveModel, LastEntity, LastModel,
     };
-    use crate::utils::db::init_db;
+    use crate::testing::memory_db;
     use china_unicom_rs::data::ChinaUnicomData;
     use sea_orm::{ActiveModelTrait, EntityTrait, ModelTrait, Set};
 
+    #[test]
+    fn validate_config() {
+        let mut config = ConfigModel {
+            user: "1".to_string(),
+            cookie: "cookie".to_string(),
+            app_id: "app_id".to_string(),
+            token_online: "token_online".to_string(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        config.interval = 0;
+        config.nonfree_threshold = Some(-1.0);
+        assert_eq!(config.validate().unwrap_err().len(), 2);
+
+        assert_eq!(config.repair(), vec!["interval", "nonfree_threshold"]);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn config_json() {
+        let config = ConfigModel {
+            user: "1".to_string(),
+            cookie: "0123456789abcdef".to_string(),
+            app_id: "0123456789abcdef".to_string(),
+            token_online: "0123456789abcdef".to_string(),
+            free_threshold: Some(1.5),
+            ..Default::default()
+        };
+        let restored = ConfigModel::from_json(&config.to_json(false), None).unwrap();
+        assert_eq!(restored.cookie, config.cookie);
+        assert_eq!(restored.free_threshold, config.free_threshold);
+        assert_eq!(restored.timeout, config.timeout);
+
+        // masked secrets can only be merged into an existing config
+        assert_eq!(
+            ConfigModel::from_json(&config.to_json(true), None)
+                .unwrap_err()
+                .len(),
+            3
+        );
+        let merged = ConfigModel::from_json(&config.to_json(true), Some(&config)).unwrap();
+        assert_eq!(merged.token_online, config.token_online);
+    }
+
+    /// an in-memory database with a config, last and daily data of user "1"
+    async fn fixture() -> sea_orm::DatabaseConnection {
+        let db = memory_db().await;
+        let config_active: ConfigActiveModel = ConfigModel {
+            user: "1".to_string(),
+            ..Default::default()
+        }
+        .into();
+        let today_active: LastActiveModel = LastModel {
+            user: "1".to_string(),
+            ..Default::default()
+        }
+        .into();
+        let yesterday_active: DailyActiveModel = DailyModel {
+            user: "1".to_string(),
+            ..Default::default()
+        }
+        .into();
+        ConfigEntity::insert(config_active).exec(&db).await.unwrap();
+        LastEntity::insert(today_active).exec(&db).await.unwrap();
+        DailyEntity::insert(yesterday_active)
+            .exec(&db)
+            .await
+            .unwrap();
+        db
+    }
+
     #[tokio::test]
     async fn init_tables() {
-        let _db = init_db().await.unwrap();
+        let _db = memory_db().await;
     }
 
     #[tokio::test]
     async fn insert_all() {
-        // delete the already exist file
-        let path = std::path::Path::new("./china_unicom/data.db");
-        if path.exists() {
-            std::fs::remove_file(path).unwrap();
-        }
-        let db = init_db().await.unwrap();
+        let db = memory_db().await;
         let config_active: ConfigActiveModel = ConfigModel {
             user: "1".to_string(),
             ..Default::default()
@@ -57,28 +137,25 @@ pub mod data_test {
             .await
             .unwrap();
 
-        println!("{:?}", config);
-        println!("{:?}", today);
-        println!("{:?}", yesterday);
+        assert_eq!(config.last_insert_id, "1");
+        assert_eq!(today.last_insert_id, "1");
+        assert_eq!(yesterday.last_insert_id, "1");
     }
 
     #[tokio::test]
     async fn get_all() {
-        let db = init_db().await.unwrap();
+        let db = fixture().await;
         let config = ConfigEntity::find().all(&db).await.unwrap();
         let today = LastEntity::find().all(&db).await.unwrap();
         let yesterday = DailyEntity::find().all(&db).await.unwrap();
-        assert!(config.len() > 0);
-        assert!(today.len() > 0);
-        assert!(yesterday.len() > 0);
-        println!("{:?}", config);
-        println!("{:?}", today);
-        println!("{:?}", yesterday);
+        assert_eq!(config.len(), 1);
+        assert_eq!(today.len(), 1);
+        assert_eq!(yesterday.len(), 1);
     }
 
     #[tokio::test]
     async fn relation_all() {
-        let db = init_db().await.unwrap();
+        let db = fixture().await;
         let today = LastEntity::find().one(&db).await.unwrap().unwrap();
         let yesterday = DailyEntity::find().one(&db).await.unwrap().unwrap();
         let config = ConfigEntity::find().one(&db).await.unwrap().unwrap();
@@ -102,15 +179,15 @@ pub mod data_test {
             .await
             .unwrap()
             .unwrap();
-        println!("{:?}", today_config);
-        println!("{:?}", yesterday_config);
-        println!("{:?}", config_today);
-        println!("{:?}", config_yesterday);
+        assert_eq!(today_config.len(), 1);
+        assert_eq!(yesterday_config.user, "1");
+        assert_eq!(config_today.user, "1");
+        assert_eq!(config_yesterday.user, "1");
     }
 
     #[tokio::test]
     async fn update_all() {
-        let db = init_db().await.unwrap();
+        let db = fixture().await;
         let today = build_last_active(ChinaUnicomData::default(), "1".to_string(), "1".to_string());
         today.update(&db).await.unwrap();
 
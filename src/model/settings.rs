@@ -0,0 +1,355 @@
+use std::fmt::Display;
+
+use serde_json::json;
+
+use crate::utils::{
+    option_t::{display_option, OptionT},
+    redact::mask_secret,
+    units::{HumanDuration, HumanSize},
+};
+
+use super::{
+    config::{MAX_INTERVAL, MAX_THRESHOLD, MAX_TIMEOUT, MIN_INTERVAL, MIN_TIMEOUT},
+    ConfigModel,
+};
+
+/// the type of a setting value, with its bounds
+#[derive(Clone, Copy, Debug)]
+pub enum SettingKind {
+    /// non-empty text
+    Text,
+    /// duration in seconds, `min <= value <= max`
+    Duration { min: i64, max: i64 },
+    /// size in GB, `0 < value <= max`
+    Size { max: f64 },
+}
+
+impl SettingKind {
+    pub fn type_hint(&self) -> &'static str {
+        match self {
+            SettingKind::Text => "text",
+            SettingKind::Duration { .. } => "duration(e.g. 300, 30m, 2h)",
+            SettingKind::Size { .. } => "size(e.g. 1.5G, 500MB)",
+        }
+    }
+
+    pub fn bounds_hint(&self) -> Option<String> {
+        match self {
+            SettingKind::Text => None,
+            SettingKind::Duration { min, max } => Some(format!(
+                "between {} and {}",
+                HumanDuration(*min),
+                HumanDuration(*max)
+            )),
+            SettingKind::Size { max } => {
+                Some(format!("greater than 0 and at most {}", HumanSize(*max)))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingValue {
+    Text(String),
+    Duration(Option<i64>),
+    Size(Option<f64>),
+}
+
+impl Display for SettingValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingValue::Text(text) => write!(f, "{}", text),
+            SettingValue::Duration(duration) => {
+                write!(f, "{}", display_option(duration.map(HumanDuration)))
+            }
+            SettingValue::Size(size) => write!(f, "{}", display_option(size.map(HumanSize))),
+        }
+    }
+}
+
+impl SettingValue {
+    /// durations are in seconds and sizes are in GB, None is null
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            SettingValue::Text(text) => json!(text),
+            SettingValue::Duration(duration) => json!(duration),
+            SettingValue::Size(size) => json!(size),
+        }
+    }
+
+    /// the reverse of [`SettingValue::to_json`], None if the json does not match the kind
+    pub fn from_json(kind: SettingKind, value: &serde_json::Value) -> Option<Self> {
+        match kind {
+            SettingKind::Text => value
+                .as_str()
+                .map(|text| SettingValue::Text(text.to_string())),
+            SettingKind::Duration { .. } if value.is_null() => Some(SettingValue::Duration(None)),
+            SettingKind::Duration { .. } => value.as_i64().map(|d| SettingValue::Duration(Some(d))),
+            SettingKind::Size { .. } if value.is_null() => Some(SettingValue::Size(None)),
+            SettingKind::Size { .. } => value.as_f64().map(|s| SettingValue::Size(Some(s))),
+        }
+    }
+}
+
+/// A user editable field of [`ConfigModel`]
+pub struct Setting {
+    /// the key used in `config set <key>`
+    pub name: &'static str,
+    /// the name shown in `config show`
+    pub label: &'static str,
+    pub kind: SettingKind,
+    /// whether the setting can be set to None
+    pub optional: bool,
+    /// whether the value should be masked when shown
+    pub secret: bool,
+    pub description: &'static str,
+    pub get: fn(&ConfigModel) -> SettingValue,
+    pub set: fn(&mut ConfigModel, SettingValue),
+}
+
+impl Setting {
+    /// parse the text value and check the bounds
+    pub fn parse(&self, value: &str) -> Result<SettingValue, String> {
+        let value = value.trim();
+        let parsed = match self.kind {
+            SettingKind::Text => SettingValue::Text(value.to_string()),
+            SettingKind::Duration { .. } => SettingValue::Duration(if self.optional {
+                value.parse::<OptionT<HumanDuration>>()?.0.map(|d| d.0)
+            } else {
+                Some(value.parse::<HumanDuration>()?.0)
+            }),
+            SettingKind::Size { .. } => SettingValue::Size(if self.optional {
+                value.parse::<OptionT<HumanSize>>()?.0.map(|s| s.0)
+            } else {
+                Some(value.parse::<HumanSize>()?.0)
+            }),
+        };
+        self.check_value(&parsed)?;
+        Ok(parsed)
+    }
+
+    pub fn check_value(&self, value: &SettingValue) -> Result<(), String> {
+        match (self.kind, value) {
+            (SettingKind::Text, SettingValue::Text(text)) => {
+                if text.trim().is_empty() {
+                    return Err(format!("{} can not be empty.", self.label));
+                }
+            }
+            (SettingKind::Duration { min, max }, SettingValue::Duration(Some(duration))) => {
+                if *duration < min || *duration > max {
+                    return Err(format!(
+                        "{} must be {}, got {}.",
+                        self.label,
+                        self.kind.bounds_hint().unwrap_or_default(),
+                        HumanDuration(*duration)
+                    ));
+                }
+            }
+            (SettingKind::Size { max }, SettingValue::Size(Some(size))) => {
+                if !size.is_finite() || *size <= 0.0 || *size > max {
+                    return Err(format!(
+                        "{} must be {}, got {}.",
+                        self.label,
+                        self.kind.bounds_hint().unwrap_or_default(),
+                        HumanSize(*size)
+                    ));
+                }
+            }
+            (SettingKind::Duration { .. }, SettingValue::Duration(None))
+            | (SettingKind::Size { .. }, SettingValue::Size(None)) => {
+                if !self.optional {
+                    return Err(format!("{} can not be None.", self.label));
+                }
+            }
+            _ => return Err(format!("Invalid value type for {}.", self.label)),
+        }
+        Ok(())
+    }
+
+    /// check the current value of the config
+    pub fn check(&self, config: &ConfigModel) -> Result<(), String> {
+        self.check_value(&(self.get)(config))
+    }
+
+    /// parse the text value and apply it to the config, return the normalized value
+    pub fn apply(&self, config: &mut ConfigModel, value: &str) -> Result<String, String> {
+        let value = self.parse(value)?;
+        let normalized = value.to_string();
+        (self.set)(config, value);
+        Ok(normalized)
+    }
+
+    pub fn display(&self, config: &ConfigModel) -> String {
+        (self.get)(config).to_string()
+    }
+
+    /// display the value, with secret masked
+    pub fn display_redacted(&self, config: &ConfigModel) -> String {
+        if self.secret {
+            mask_secret(&self.display(config))
+        } else {
+            self.display(config)
+        }
+    }
+
+    /// the json value, with secret masked
+    pub fn json_redacted(&self, config: &ConfigModel) -> serde_json::Value {
+        if self.secret {
+            json!(mask_secret(&self.display(config)))
+        } else {
+            (self.get)(config).to_json()
+        }
+    }
+
+    /// the hint sent when the value can not be parsed
+    pub fn hint(&self) -> String {
+        let mut hint = format!("Please enter a valid {}", self.kind.type_hint());
+        if self.optional {
+            hint += " or 'none'";
+        }
+        hint += &format!(" for {}", self.name);
+        if let Some(bounds) = self.kind.bounds_hint() {
+            hint += &format!(", {}", bounds);
+        }
+        hint += ".";
+        hint
+    }
+}
+
+pub static SETTINGS: [Setting; 5] = [
+    Setting {
+        name: "cookie",
+        label: "Cookie",
+        kind: SettingKind::Text,
+        optional: false,
+        secret: true,
+        description: "China Unicom Cookie",
+        get: |config| SettingValue::Text(config.cookie.clone()),
+        set: |config, value| {
+            if let SettingValue::Text(cookie) = value {
+                config.cookie = cookie;
+            }
+        },
+    },
+    Setting {
+        name: "interval",
+        label: "Interval",
+        kind: SettingKind::Duration {
+            min: MIN_INTERVAL,
+            max: MAX_INTERVAL,
+        },
+        optional: false,
+        secret: false,
+        description: "Query interval of the task",
+        get: |config| SettingValue::Duration(Some(config.interval)),
+        set: |config, value| {
+            if let SettingValue::Duration(Some(interval)) = value {
+                config.interval = interval;
+            }
+        },
+    },
+    Setting {
+        name: "timeout",
+        label: "Timeout",
+        kind: SettingKind::Duration {
+            min: MIN_TIMEOUT,
+            max: MAX_TIMEOUT,
+        },
+        optional: true,
+        secret: false,
+        description: "Notify when no notification was sent for this long",
+        get: |config| SettingValue::Duration(config.timeout),
+        set: |config, value| {
+            if let SettingValue::Duration(timeout) = value {
+                config.timeout = timeout;
+            }
+        },
+    },
+    Setting {
+        name: "free_threshold",
+        label: "Free threshold",
+        kind: SettingKind::Size { max: MAX_THRESHOLD },
+        optional: true,
+        secret: false,
+        description: "Notify when the free flow used since last notification exceeds this",
+        get: |config| SettingValue::Size(config.free_threshold),
+        set: |config, value| {
+            if let SettingValue::Size(free_threshold) = value {
+                config.free_threshold = free_threshold;
+            }
+        },
+    },
+    Setting {
+        name: "nonfree_threshold",
+        label: "Nonfree threshold",
+        kind: SettingKind::Size { max: MAX_THRESHOLD },
+        optional: true,
+        secret: false,
+        description: "Notify when the nonfree flow used since last notification exceeds this",
+        get: |config| SettingValue::Size(config.nonfree_threshold),
+        set: |config, value| {
+            if let SettingValue::Size(nonfree_threshold) = value {
+                config.nonfree_threshold = nonfree_threshold;
+            }
+        },
+    },
+];
+
+/// find a setting by name, `-` and `_` are treated the same
+pub fn find_setting(name: &str) -> Option<&'static Setting> {
+    let name = name.trim().to_lowercase().replace('-', "_");
+    SETTINGS.iter().find(|setting| setting.name == name)
+}
+
+/// clap value parser of setting names
+pub fn parse_setting_name(name: &str) -> Result<&'static Setting, String> {
+    find_setting(name).ok_or(format!(
+        "Unknown setting '{}', available: {}",
+        name,
+        SETTINGS
+            .iter()
+            .map(|setting| setting.name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+/// the numbered menu of the interactive config set
+pub fn menu() -> String {
+    let mut menu = "Please send a option number to set:\n".to_string();
+    for (index, setting) in SETTINGS.iter().enumerate() {
+        menu += &format!(
+            "{}.{}: {}",
+            index + 1,
+            setting.name,
+            setting.kind.type_hint()
+        );
+        if setting.optional {
+            menu += " or None";
+        }
+        menu += "\n";
+    }
+    menu += "\nSend 0 to cancel";
+    menu
+}
+
+/// the setting of a menu option number, starting from 1
+pub fn from_option(option: u8) -> Option<&'static Setting> {
+    SETTINGS.get((option as usize).checked_sub(1)?)
+}
+
+/// the help text listing all settings
+pub fn help() -> String {
+    let mut help = "Settings:\n".to_string();
+    for setting in SETTINGS.iter() {
+        help += &format!("  {:<18} {}", setting.name, setting.kind.type_hint());
+        if setting.optional {
+            help += " or None";
+        }
+        if let Some(bounds) = setting.kind.bounds_hint() {
+            help += &format!(", {}", bounds);
+        }
+        help += &format!("\n  {:<18} {}\n", "", setting.description);
+    }
+    help
+}
@@ -0,0 +1,139 @@
+use chrono::{DateTime, Local};
+use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+
+/// the kind of a delivery target
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum TargetKind {
+    /// the private chat of the user on the bot
+    #[sea_orm(string_value = "private")]
+    Private,
+    /// a group on the bot
+    #[sea_orm(string_value = "group")]
+    Group,
+    /// an http url, the usage is posted as json
+    #[sea_orm(string_value = "webhook")]
+    Webhook,
+    /// an email address, sent by the smtp server of the bot
+    #[sea_orm(string_value = "email")]
+    Email,
+}
+
+impl std::fmt::Display for TargetKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
+/// where the notifications of a user are delivered
+#[derive(Clone, Debug, DeriveEntityModel)]
+#[sea_orm(table_name = "target")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+
+    pub user: String,
+    pub kind: TargetKind,
+    // 私聊为 {server}_{user_id}, 群组为 {server}_{group_id}, webhook 为 url, 邮件为邮箱地址
+    pub address: String,
+    // 发送通知的bot, 非聊天渠道为空
+    pub bot: String,
+    // 渠道的其他选项(json)
+    pub options: String,
+    pub enabled: bool,
+    // 连续失败次数, 成功后清零
+    pub failures: i32,
+    pub last_error: Option<String>,
+    pub last_failure: Option<DateTime<Local>>,
+}
+
+impl Model {
+    /// a short description of the target, secrets in the options are not shown
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "#{} {} {}{}",
+            self.id,
+            self.kind,
+            self.address,
+            if self.enabled { "" } else { " (disabled)" }
+        );
+        if self.failures > 0 {
+            text += &format!(
+                ", {} failures, last: {}",
+                self.failures,
+                self.last_error.as_deref().unwrap_or_default()
+            );
+        }
+        text
+    }
+}
+
+/// an enabled target without options
+pub fn build_target_active(
+    user: String,
+    kind: TargetKind,
+    address: String,
+    bot: String,
+) -> super::TargetActiveModel {
+    super::TargetActiveModel {
+        id: NotSet,
+        user: Set(user),
+        kind: Set(kind),
+        address: Set(address),
+        bot: Set(bot),
+        options: Set("{}".to_string()),
+        enabled: Set(true),
+        failures: Set(0),
+        last_error: Set(None),
+        last_failure: Set(None),
+    }
+}
+
+/// add the private chat of the user as a target if not exist
+pub async fn ensure_private_target<C: ConnectionTrait>(
+    db: &C,
+    user: &str,
+    bot: &str,
+) -> Result<(), DbErr> {
+    let exist = Entity::find()
+        .filter(Column::User.eq(user))
+        .filter(Column::Kind.eq(TargetKind::Private))
+        .one(db)
+        .await?
+        .is_some();
+    if !exist {
+        Entity::insert(build_target_active(
+            user.to_string(),
+            TargetKind::Private,
+            user.to_string(),
+            bot.to_string(),
+        ))
+        .exec(db)
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Config,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Relation::Config => Entity::belongs_to(super::config::Entity)
+                .from(Column::User)
+                .to(super::config::Column::User)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::config::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Config.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
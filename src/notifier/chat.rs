@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+
+use crate::{
+    model::{target::TargetKind, TargetModel},
+    utils::sink::SharedSink,
+};
+
+use super::{Notification, Notifier};
+
+/// deliver to the private chats and groups on the bots
+#[derive(Clone)]
+pub struct ChatNotifier {
+    sink: SharedSink,
+}
+
+impl ChatNotifier {
+    pub fn new(sink: SharedSink) -> Self {
+        Self { sink }
+    }
+}
+
+#[async_trait]
+impl Notifier for ChatNotifier {
+    fn handles(&self, kind: TargetKind) -> bool {
+        matches!(kind, TargetKind::Private | TargetKind::Group)
+    }
+
+    async fn notify(
+        &self,
+        target: &TargetModel,
+        notification: &Notification,
+    ) -> anyhow::Result<()> {
+        match target.kind {
+            TargetKind::Private => {
+                self.sink
+                    .send_private(&target.address, &target.bot, notification.text.clone())
+                    .await
+            }
+            // the members of the group need to know whose notification it is
+            TargetKind::Group => {
+                self.sink
+                    .send_group(
+                        &target.address,
+                        &target.bot,
+                        format!("{}:\n{}", notification.user, notification.text),
+                    )
+                    .await
+            }
+            kind => Err(anyhow::anyhow!("Can't deliver to {} targets by chat", kind)),
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::{
+        authentication::Credentials,
+        client::{Tls, TlsParameters},
+    },
+    Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use rand::Rng;
+use sea_orm::EntityTrait;
+
+use crate::model::{
+    target::{build_target_active, TargetKind},
+    TargetEntity, TargetModel,
+};
+
+use super::{Notification, Notifier};
+
+/// how the connection to the smtp server is secured
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum SmtpTls {
+    /// plain text, only for local servers
+    None,
+    /// upgrade a plain connection by `STARTTLS`, the default port is 587
+    StartTls,
+    /// tls from the start, the default port is 465
+    #[default]
+    Tls,
+}
+
+/// the smtp server sending the emails, set by the admin
+#[derive(Clone, Debug)]
+pub struct SmtpOptions {
+    pub host: String,
+    /// default to the port of the tls mode
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub tls: SmtpTls,
+    /// the sender, e.g. `ChinaUnicom <bot@example.com>`
+    pub from: String,
+}
+
+impl SmtpOptions {
+    pub fn new(host: impl Into<String>, from: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port: None,
+            username: None,
+            password: None,
+            tls: SmtpTls::default(),
+            from: from.into(),
+        }
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn tls(mut self, tls: SmtpTls) -> Self {
+        self.tls = tls;
+        self
+    }
+}
+
+/// the subject tells the scheduled reports from the threshold alerts
+pub fn email_subject(notification: &Notification) -> String {
+    match &notification.event {
+        Some(event)
+            if event
+                .fired
+                .iter()
+                .all(|name| *name == "timeout" || *name == "no_last") =>
+        {
+            format!("ChinaUnicom usage report: {}", event.data.package_name)
+        }
+        Some(event) => format!("ChinaUnicom usage alert: {}", event.data.package_name),
+        None => "ChinaUnicom notification".to_string(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// the html body, the lines of the text are kept
+pub fn email_html(notification: &Notification) -> String {
+    let lines: Vec<String> = notification.text.lines().map(escape_html).collect();
+    format!("<html><body><p>{}</p></body></html>", lines.join("<br>\n"))
+}
+
+/// parse an email address typed by the user
+pub fn parse_address(address: &str) -> anyhow::Result<Address> {
+    address
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid email address: {}", e))
+}
+
+/// a random code of 6 digits, sent to an address to confirm the user owns it
+pub fn confirmation_code() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+/// add an email target of the user
+pub async fn add_email_target(
+    db: &sea_orm::DatabaseConnection,
+    user: &str,
+    address: &str,
+) -> anyhow::Result<TargetModel> {
+    let address = parse_address(address)?;
+    let target_active = build_target_active(
+        user.to_string(),
+        TargetKind::Email,
+        address.to_string(),
+        String::new(),
+    );
+    Ok(TargetEntity::insert(target_active)
+        .exec_with_returning(db)
+        .await?)
+}
+
+/// send the notifications to the email targets as plain text and html
+#[derive(Clone)]
+pub struct EmailNotifier {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl EmailNotifier {
+    pub fn new(options: &SmtpOptions) -> anyhow::Result<Self> {
+        let tls = match options.tls {
+            SmtpTls::None => Tls::None,
+            SmtpTls::StartTls => Tls::Required(TlsParameters::new(options.host.clone())?),
+            SmtpTls::Tls => Tls::Wrapper(TlsParameters::new(options.host.clone())?),
+        };
+        let port = options.port.unwrap_or(match options.tls {
+            SmtpTls::None => 25,
+            SmtpTls::StartTls => 587,
+            SmtpTls::Tls => 465,
+        });
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&options.host)
+            .port(port)
+            .tls(tls)
+            .timeout(Some(Duration::from_secs(10)));
+        if let (Some(username), Some(password)) = (&options.username, &options.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(Self {
+            from: options
+                .from
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid sender {}: {}", options.from, e))?,
+            transport: builder.build(),
+        })
+    }
+
+    /// send the confirmation code before the address is added as a target
+    pub async fn send_code(&self, address: &Address, code: &str) -> anyhow::Result<()> {
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(Mailbox::new(None, address.clone()))
+            .subject("ChinaUnicom email confirmation")
+            .body(format!(
+                "Your confirmation code is {}.\nSend it to the bot to receive the notifications at this address, ignore this email if you did not ask for it.",
+                code
+            ))?;
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn handles(&self, kind: TargetKind) -> bool {
+        kind == TargetKind::Email
+    }
+
+    async fn notify(
+        &self,
+        target: &TargetModel,
+        notification: &Notification,
+    ) -> anyhow::Result<()> {
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(target.address.parse()?)
+            .subject(email_subject(notification))
+            .multipart(MultiPart::alternative_plain_html(
+                notification.text.clone(),
+                email_html(notification),
+            ))?;
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::{
+        add_email_target, confirmation_code, parse_address, EmailNotifier, SmtpOptions, SmtpTls,
+    };
+    use crate::{
+        notifier::{Notification, Notifier, UsageEvent},
+        testing::{at, memory_db, usage},
+    };
+
+    /// a local smtp stand-in accepting every mail, return the port and the received
+    /// `(recipients, data)`
+    async fn listen() -> (u16, Arc<Mutex<Vec<(Vec<String>, String)>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+                let mut recipients = Vec::new();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let command = line.to_ascii_uppercase();
+                    let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO")
+                    {
+                        b"250 localhost\r\n"
+                    } else if command.starts_with("RCPT TO:") {
+                        recipients.push(line[8..].trim_matches(['<', '>', ' ']).to_string());
+                        b"250 OK\r\n"
+                    } else if command == "DATA" {
+                        writer.write_all(b"354 End with .\r\n").await.unwrap();
+                        let mut data = Vec::new();
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            if line == "." {
+                                break;
+                            }
+                            data.push(line);
+                        }
+                        received_clone
+                            .lock()
+                            .unwrap()
+                            .push((std::mem::take(&mut recipients), data.join("\n")));
+                        b"250 OK\r\n"
+                    } else if command == "QUIT" {
+                        writer.write_all(b"221 Bye\r\n").await.unwrap();
+                        break;
+                    } else {
+                        b"250 OK\r\n"
+                    };
+                    writer.write_all(reply).await.unwrap();
+                }
+            }
+        });
+        (port, received)
+    }
+
+    #[tokio::test]
+    async fn send_alert() {
+        let db = memory_db().await;
+        let (port, received) = listen().await;
+        let options = SmtpOptions::new("127.0.0.1", "ChinaUnicom <bot@example.com>")
+            .port(port)
+            .tls(SmtpTls::None);
+        let notifier = EmailNotifier::new(&options).unwrap();
+        let target = add_email_target(&db, "test_1", " user@example.com ")
+            .await
+            .unwrap();
+        assert!(add_email_target(&db, "test_1", "not an address")
+            .await
+            .is_err());
+
+        let notification =
+            Notification::new("test_1", "used a <lot>".to_string()).with_event(UsageEvent {
+                data: usage(at(1, 9, 0), 1.5),
+                last: None,
+                daily: None,
+                fired: vec!["nonfree_threshold"],
+            });
+        notifier.notify(&target, &notification).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let (recipients, data) = &received[0];
+        assert_eq!(recipients, &vec!["user@example.com".to_string()]);
+        assert!(data.contains("Subject: ChinaUnicom usage alert: test package"));
+        assert!(data.contains("multipart/alternative"));
+        assert!(data.contains("text/plain"));
+        assert!(data.contains("used a &lt;lot&gt;"));
+    }
+
+    #[tokio::test]
+    async fn send_code() {
+        let (port, received) = listen().await;
+        let options = SmtpOptions::new("127.0.0.1", "bot@example.com")
+            .port(port)
+            .tls(SmtpTls::None);
+        let notifier = EmailNotifier::new(&options).unwrap();
+        let code = confirmation_code();
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+        let address = parse_address(" user@example.com ").unwrap();
+        notifier.send_code(&address, &code).await.unwrap();
+        assert!(parse_address("not an address").is_err());
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let (recipients, data) = &received[0];
+        assert_eq!(recipients, &vec!["user@example.com".to_string()]);
+        assert!(data.contains("Subject: ChinaUnicom email confirmation"));
+        assert!(data.contains(&code));
+    }
+}
@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use china_unicom_rs::data::ChinaUnicomData;
+use chrono::{DateTime, Local};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, Set,
+};
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use crate::model::{
+    target::{Column, TargetKind},
+    DailyModel, LastModel, TargetEntity, TargetModel,
+};
+
+pub mod chat;
+#[cfg(feature = "email")]
+pub mod email;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+pub use chat::ChatNotifier;
+#[cfg(feature = "email")]
+pub use email::EmailNotifier;
+#[cfg(feature = "webhook")]
+pub use webhook::WebhookNotifier;
+
+/// the usage behind a notification, for the channels delivering structured data
+#[derive(Clone, Debug)]
+pub struct UsageEvent {
+    pub data: ChinaUnicomData,
+    /// the data of the last notification, before this query
+    pub last: Option<LastModel>,
+    /// the data at the start of the day, before this query
+    pub daily: Option<DailyModel>,
+    /// the names of the fired thresholds, `no_last` if there is no last data
+    pub fired: Vec<&'static str>,
+}
+
+/// all the fields of the data
+#[derive(Clone, Debug, Serialize)]
+pub struct UsageFields {
+    pub time: DateTime<Local>,
+    pub sum_flow_used: f64,
+    pub limit_flow_used: f64,
+    pub non_limit_flow_used: f64,
+    pub free_flow_used: f64,
+    pub non_free_flow_used: f64,
+    pub sum_flow: f64,
+    pub limit_flow: f64,
+    pub non_limit_flow: f64,
+    pub sum_voice_used: i64,
+    pub limit_voice_used: i64,
+    pub non_limit_voice_used: i64,
+    pub sum_voice: i64,
+    pub limit_voice: i64,
+    pub non_limit_voice: i64,
+}
+
+impl From<&ChinaUnicomData> for UsageFields {
+    fn from(data: &ChinaUnicomData) -> Self {
+        Self {
+            time: data.time,
+            sum_flow_used: data.sum_flow_used,
+            limit_flow_used: data.limit_flow_used,
+            non_limit_flow_used: data.non_limit_flow_used,
+            free_flow_used: data.free_flow_used,
+            non_free_flow_used: data.non_free_flow_used,
+            sum_flow: data.sum_flow,
+            limit_flow: data.limit_flow,
+            non_limit_flow: data.non_limit_flow,
+            sum_voice_used: data.sum_voice_used,
+            limit_voice_used: data.limit_voice_used,
+            non_limit_voice_used: data.non_limit_voice_used,
+            sum_voice: data.sum_voice,
+            limit_voice: data.limit_voice,
+            non_limit_voice: data.non_limit_voice,
+        }
+    }
+}
+
+/// a message to the user
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub user: String,
+    pub text: String,
+    /// `None` for the messages not about usage, e.g. a failed task
+    pub event: Option<UsageEvent>,
+}
+
+impl Notification {
+    pub fn new(user: &str, text: String) -> Self {
+        Self {
+            user: user.to_string(),
+            text,
+            event: None,
+        }
+    }
+
+    pub fn with_event(mut self, event: UsageEvent) -> Self {
+        self.event = Some(event);
+        self
+    }
+}
+
+/// deliver notifications to the targets of some kinds
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// whether the notifier delivers to this kind of targets
+    fn handles(&self, kind: TargetKind) -> bool;
+
+    async fn notify(&self, target: &TargetModel, notification: &Notification)
+        -> anyhow::Result<()>;
+}
+
+/// the notifiers of all the kinds of targets
+#[derive(Clone, Default)]
+pub struct Notifiers(Vec<Arc<dyn Notifier>>);
+
+impl Notifiers {
+    pub fn with(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.0.push(Arc::new(notifier));
+        self
+    }
+
+    fn find(&self, kind: TargetKind) -> Option<&Arc<dyn Notifier>> {
+        self.0.iter().find(|notifier| notifier.handles(kind))
+    }
+
+    /// send to one target, the failure of the target is tracked in the database
+    pub async fn notify_target(
+        &self,
+        db: &sea_orm::DatabaseConnection,
+        target: &TargetModel,
+        notification: &Notification,
+    ) -> anyhow::Result<()> {
+        let result = match self.find(target.kind) {
+            Some(notifier) => notifier.notify(target, notification).await,
+            None => Err(anyhow::anyhow!("No notifier for {} targets", target.kind)),
+        };
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_notification(target.kind, result.is_ok());
+        let mut target_active = target.clone().into_active_model();
+        match &result {
+            Ok(_) if target.failures == 0 => return Ok(()),
+            Ok(_) => {
+                target_active.failures = Set(0);
+            }
+            Err(e) => {
+                target_active.failures = Set(target.failures + 1);
+                target_active.last_error = Set(Some(e.to_string()));
+                target_active.last_failure = Set(Some(Local::now()));
+            }
+        }
+        target_active.update(db).await?;
+        result
+    }
+
+    /// send to the enabled targets of the user, fails only if no target is delivered
+    pub async fn dispatch(
+        &self,
+        db: &sea_orm::DatabaseConnection,
+        notification: &Notification,
+    ) -> anyhow::Result<usize> {
+        let targets = TargetEntity::find()
+            .filter(Column::User.eq(notification.user.as_str()))
+            .filter(Column::Enabled.eq(true))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?;
+        // the targets are notified together, so a slow target does not hold up the others
+        let mut deliveries = JoinSet::new();
+        for target in targets.iter().cloned() {
+            let notifiers = self.clone();
+            let db = db.clone();
+            let notification = notification.clone();
+            deliveries.spawn(async move {
+                let result = notifiers.notify_target(&db, &target, &notification).await;
+                (target.id, result)
+            });
+        }
+        let mut results = Vec::new();
+        while let Some(result) = deliveries.join_next().await {
+            results.push(result?);
+        }
+        results.sort_by_key(|(id, _)| *id);
+
+        let mut delivered = 0;
+        let mut errors = Vec::new();
+        for (id, result) in results {
+            match result {
+                Ok(_) => delivered += 1,
+                Err(e) => {
+                    tracing::error!(
+                        "ChinaUnicom: Notify target {} of user {} failed: {}",
+                        id,
+                        notification.user,
+                        e
+                    );
+                    errors.push(format!("#{}: {}", id, e));
+                }
+            }
+        }
+        if delivered == 0 && !errors.is_empty() {
+            return Err(anyhow::anyhow!("All targets failed: {}", errors.join("; ")));
+        }
+        if targets.is_empty() {
+            tracing::warn!(
+                "ChinaUnicom: User {} has no enabled target",
+                notification.user
+            );
+        }
+        Ok(delivered)
+    }
+}
@@ -0,0 +1,607 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use china_unicom_rs::data::ChinaUnicomData;
+use chrono::{DateTime, Local};
+use hmac::{Hmac, Mac};
+use sea_orm::{EntityTrait, Set};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    model::{
+        target::{build_target_active, TargetKind},
+        TargetEntity, TargetModel,
+    },
+    utils::history::UsageDelta,
+};
+
+use super::{Notification, Notifier, UsageEvent, UsageFields};
+
+/// the header of the hmac-sha256 signature of the body, in the form of `sha256={hex}`
+pub const SIGNATURE_HEADER: &str = "X-ChinaUnicom-Signature";
+
+const DEFAULT_RETRIES: u32 = 3;
+
+/// the retries of a target are limited, the retry time bounds them anyway
+pub const MAX_RETRIES: u32 = 10;
+
+/// the longest wait between two attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// no retry is started after this, so a delivery ends within 45s with the request timeout,
+/// before the next query of the shortest interval
+const DEFAULT_RETRY_TIME: Duration = Duration::from_secs(35);
+
+/// the options of a webhook target, stored in `TargetModel::options`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WebhookOptions {
+    /// the body is signed with the secret if set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// the retries after the first attempt failed, at most [`MAX_RETRIES`]
+    #[serde(default = "default_retries", deserialize_with = "deserialize_retries")]
+    pub retries: u32,
+    /// post to loopback, private and link-local addresses too, only for the targets added by
+    /// admins
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_private: bool,
+}
+
+fn default_retries() -> u32 {
+    DEFAULT_RETRIES
+}
+
+/// clamp the retries stored before the limit
+fn deserialize_retries<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    Ok(u32::deserialize(deserializer)?.min(MAX_RETRIES))
+}
+
+impl Default for WebhookOptions {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            retries: DEFAULT_RETRIES,
+            allow_private: false,
+        }
+    }
+}
+
+/// the usage between an earlier data and the new data
+#[derive(Clone, Debug, Serialize)]
+pub struct UsageSince {
+    /// the time of the earlier data
+    pub since: DateTime<Local>,
+    pub seconds: i64,
+    #[serde(flatten)]
+    pub delta: UsageDelta,
+}
+
+impl UsageSince {
+    pub fn between(old: &ChinaUnicomData, new: &ChinaUnicomData) -> Self {
+        Self {
+            since: old.time,
+            seconds: (new.time - old.time).num_seconds(),
+            delta: UsageDelta::from(new).since(&UsageDelta::from(old)),
+        }
+    }
+}
+
+/// the usage part of the payload
+#[derive(Clone, Debug, Serialize)]
+pub struct UsagePayload {
+    pub package_name: String,
+    pub data: UsageFields,
+    /// since the last notification
+    pub delta_last: Option<UsageSince>,
+    /// since the start of the day
+    pub delta_daily: Option<UsageSince>,
+    /// the names of the fired thresholds
+    pub fired: Vec<&'static str>,
+}
+
+impl From<&UsageEvent> for UsagePayload {
+    fn from(event: &UsageEvent) -> Self {
+        let delta = |old: ChinaUnicomData| UsageSince::between(&old, &event.data);
+        Self {
+            package_name: event.data.package_name.clone(),
+            data: UsageFields::from(&event.data),
+            delta_last: event.last.clone().map(|last| delta(last.into())),
+            delta_daily: event.daily.clone().map(|daily| delta(daily.into())),
+            fired: event.fired.clone(),
+        }
+    }
+}
+
+/// the json posted to the webhook, the usage fields are absent for messages not about usage
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookPayload {
+    pub user: String,
+    pub text: String,
+    #[serde(flatten)]
+    pub usage: Option<UsagePayload>,
+}
+
+impl From<&Notification> for WebhookPayload {
+    fn from(notification: &Notification) -> Self {
+        Self {
+            user: notification.user.clone(),
+            text: notification.text.clone(),
+            usage: notification.event.as_ref().map(UsagePayload::from),
+        }
+    }
+}
+
+/// the hex hmac-sha256 of the body
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// whether the address is only reachable from the host or its network
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // the shared address space of carrier-grade nat, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local fc00::/7 and link-local fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+            }
+        },
+    }
+}
+
+/// resolve the host of the url, fail if any of the addresses is private
+async fn resolve_public(url: &reqwest::Url) -> anyhow::Result<Vec<SocketAddr>> {
+    let host = url
+        .host_str()
+        .ok_or(anyhow::anyhow!("Invalid url: no host"))?;
+    // the brackets of an ipv6 host are not resolvable
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| anyhow::anyhow!("Cannot resolve {}: {}", host, e))?
+        .collect();
+    if let Some(address) = addresses.iter().find(|address| is_private_ip(address.ip())) {
+        return Err(anyhow::anyhow!(
+            "Invalid url: {} is a private address, only admins can add it",
+            address.ip()
+        ));
+    }
+    Ok(addresses)
+}
+
+/// add a webhook target of the user, the url must be http or https, and resolve to public
+/// addresses unless allowed by the options
+pub async fn add_webhook_target(
+    db: &sea_orm::DatabaseConnection,
+    user: &str,
+    url: &str,
+    options: &WebhookOptions,
+) -> anyhow::Result<TargetModel> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| anyhow::anyhow!("Invalid url: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow::anyhow!(
+            "Invalid url: only http and https are supported"
+        ));
+    }
+    if options.retries > MAX_RETRIES {
+        return Err(anyhow::anyhow!(
+            "The retries must be at most {}",
+            MAX_RETRIES
+        ));
+    }
+    if !options.allow_private {
+        resolve_public(&parsed).await?;
+    }
+    let mut target_active = build_target_active(
+        user.to_string(),
+        TargetKind::Webhook,
+        url.to_string(),
+        String::new(),
+    );
+    target_active.options = Set(serde_json::to_string(options)?);
+    Ok(TargetEntity::insert(target_active)
+        .exec_with_returning(db)
+        .await?)
+}
+
+/// post the notifications to the webhook targets, retry with doubled backoff within the
+/// retry time
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    backoff: Duration,
+    retry_time: Duration,
+}
+
+/// redirects are not followed, they could lead to a private address
+fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+}
+
+impl Default for WebhookNotifier {
+    fn default() -> Self {
+        Self {
+            client: client_builder()
+                .build()
+                .expect("the http client should be built"),
+            backoff: Duration::from_secs(1),
+            retry_time: DEFAULT_RETRY_TIME,
+        }
+    }
+}
+
+impl WebhookNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the wait before the first retry, default to 1s
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// no retry is started after this since the first attempt, default to 35s
+    pub fn retry_time(mut self, retry_time: Duration) -> Self {
+        self.retry_time = retry_time;
+        self
+    }
+
+    /// the client of the target, a target without private addresses allowed is resolved again
+    /// and pinned to the checked addresses, as the dns may have changed since it was added
+    async fn client(&self, url: &str, options: &WebhookOptions) -> anyhow::Result<reqwest::Client> {
+        if options.allow_private {
+            return Ok(self.client.clone());
+        }
+        let parsed = reqwest::Url::parse(url)?;
+        let addresses = resolve_public(&parsed).await?;
+        let host = parsed.host_str().unwrap_or_default();
+        Ok(client_builder()
+            .resolve_to_addrs(host, &addresses)
+            .build()?)
+    }
+
+    async fn post(
+        client: &reqwest::Client,
+        url: &str,
+        body: &[u8],
+        signature: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(signature) = signature {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", signature));
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Webhook responded {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn handles(&self, kind: TargetKind) -> bool {
+        kind == TargetKind::Webhook
+    }
+
+    async fn notify(
+        &self,
+        target: &TargetModel,
+        notification: &Notification,
+    ) -> anyhow::Result<()> {
+        let options: WebhookOptions = serde_json::from_str(&target.options)?;
+        let body = serde_json::to_vec(&WebhookPayload::from(notification))?;
+        let signature = options.secret.as_deref().map(|secret| sign(secret, &body));
+
+        let client = self.client(&target.address, &options).await?;
+        let start = Instant::now();
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match Self::post(&client, &target.address, &body, signature.as_deref()).await {
+                Ok(_) => return Ok(()),
+                Err(e)
+                    if attempt >= options.retries
+                        || start.elapsed() + backoff > self.retry_time =>
+                {
+                    return Err(e)
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "ChinaUnicom: [Retry: {}]Post webhook {} failed: {}",
+                        attempt + 1,
+                        target.id,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use serde_json::Value;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::{
+        add_webhook_target, is_private_ip, sign, WebhookNotifier, WebhookOptions, MAX_RETRIES,
+        SIGNATURE_HEADER,
+    };
+    use crate::{
+        model::{DailyModel, LastModel},
+        notifier::{Notification, Notifier, UsageEvent},
+        testing::{at, memory_db, usage},
+    };
+
+    /// a request received by the listener, with the header names in lowercase
+    struct Received {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    /// a local http listener responding the statuses in order, then 200
+    async fn listen(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = Vec::new();
+                let mut chunk = [0; 4096];
+                let header_end = loop {
+                    let read = stream.read(&mut chunk).await.unwrap();
+                    buffer.extend_from_slice(&chunk[..read]);
+                    if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+                let headers: Vec<(String, String)> = head
+                    .lines()
+                    .skip(1)
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+                    .collect();
+                let length: usize = headers
+                    .iter()
+                    .find(|(name, _)| name == "content-length")
+                    .map_or(0, |(_, value)| value.parse().unwrap());
+                while buffer.len() < header_end + length {
+                    let read = stream.read(&mut chunk).await.unwrap();
+                    buffer.extend_from_slice(&chunk[..read]);
+                }
+                received_clone.lock().unwrap().push(Received {
+                    headers,
+                    body: buffer[header_end..header_end + length].to_vec(),
+                });
+                let status = statuses.next().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn notification() -> Notification {
+        let last = LastModel {
+            time: at(1, 8, 0),
+            non_free_flow_used: 1.0,
+            sum_flow_used: 1.0,
+            ..Default::default()
+        };
+        // the daily data is from the last month, before the usage is reset
+        let daily = DailyModel {
+            time: at(1, 0, 0),
+            non_free_flow_used: 4.0,
+            sum_flow_used: 4.0,
+            ..Default::default()
+        };
+        Notification::new("test_1", "text".to_string()).with_event(UsageEvent {
+            data: usage(at(1, 9, 0), 1.5),
+            last: Some(last),
+            daily: Some(daily),
+            fired: vec!["nonfree_threshold"],
+        })
+    }
+
+    #[tokio::test]
+    async fn signed_payload_with_retries() {
+        let db = memory_db().await;
+        let (url, received) = listen(vec![500]).await;
+        let options = WebhookOptions {
+            secret: Some("secret".to_string()),
+            retries: 1,
+            allow_private: true,
+        };
+        let target = add_webhook_target(&db, "test_1", &url, &options)
+            .await
+            .unwrap();
+        let notifier = WebhookNotifier::new().backoff(Duration::from_millis(10));
+
+        notifier.notify(&target, &notification()).await.unwrap();
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+
+        let request = &received[1];
+        let signature = request
+            .headers
+            .iter()
+            .find(|(name, _)| *name == SIGNATURE_HEADER.to_lowercase())
+            .map(|(_, value)| value.clone())
+            .unwrap();
+        assert_eq!(
+            signature,
+            format!("sha256={}", sign("secret", &request.body))
+        );
+
+        let payload: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(payload["user"], "test_1");
+        assert_eq!(payload["package_name"], "test package");
+        assert_eq!(payload["data"]["non_free_flow_used"], 1.5);
+        assert_eq!(payload["delta_last"]["non_free_flow_used"], 0.5);
+        assert_eq!(payload["delta_last"]["seconds"], 3600);
+        assert_eq!(payload["delta_daily"]["non_free_flow_used"], 1.5);
+        assert_eq!(payload["delta_daily"]["sum_flow_used"], 1.5);
+        assert_eq!(payload["fired"][0], "nonfree_threshold");
+    }
+
+    #[tokio::test]
+    async fn retries_exhausted() {
+        let db = memory_db().await;
+        let (url, received) = listen(vec![500, 502]).await;
+        let options = WebhookOptions {
+            secret: None,
+            retries: 1,
+            allow_private: true,
+        };
+        let target = add_webhook_target(&db, "test_1", &url, &options)
+            .await
+            .unwrap();
+        assert!(
+            add_webhook_target(&db, "test_1", "ftp://example.com", &options)
+                .await
+                .is_err()
+        );
+        let too_many = WebhookOptions {
+            secret: None,
+            retries: MAX_RETRIES + 1,
+            allow_private: true,
+        };
+        assert!(add_webhook_target(&db, "test_1", &url, &too_many)
+            .await
+            .is_err());
+        // the options stored before the limit are clamped
+        let stored: WebhookOptions = serde_json::from_str(r#"{"retries": 1000}"#).unwrap();
+        assert_eq!(stored.retries, MAX_RETRIES);
+        let notifier = WebhookNotifier::new().backoff(Duration::from_millis(10));
+
+        let plain = Notification::new("test_1", "text".to_string());
+        assert!(notifier.notify(&target, &plain).await.is_err());
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(received[0]
+            .headers
+            .iter()
+            .all(|(name, _)| *name != SIGNATURE_HEADER.to_lowercase()));
+        let payload: Value = serde_json::from_slice(&received[0].body).unwrap();
+        assert_eq!(payload["text"], "text");
+        assert!(payload.get("data").is_none());
+    }
+
+    #[tokio::test]
+    async fn private_addresses() {
+        let db = memory_db().await;
+        let (url, received) = listen(vec![]).await;
+        let options = WebhookOptions::default();
+        for url in [
+            url.as_str(),
+            "http://localhost:8080/hook",
+            "http://10.1.2.3/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            let result = add_webhook_target(&db, "test_1", url, &options).await;
+            assert!(
+                matches!(&result, Err(e) if e.to_string().contains("private address")),
+                "{} is accepted",
+                url
+            );
+        }
+        assert!(!is_private_ip("8.8.8.8".parse().unwrap()));
+        assert!(!is_private_ip("2001:4860:4860::8888".parse().unwrap()));
+
+        // added by an admin
+        let admin = WebhookOptions {
+            allow_private: true,
+            ..Default::default()
+        };
+        let mut target = add_webhook_target(&db, "test_1", &url, &admin)
+            .await
+            .unwrap();
+        let notifier = WebhookNotifier::new();
+        let plain = Notification::new("test_1", "text".to_string());
+        notifier.notify(&target, &plain).await.unwrap();
+        assert_eq!(received.lock().unwrap().len(), 1);
+
+        // checked again when posting
+        target.options = serde_json::to_string(&options).unwrap();
+        assert!(notifier.notify(&target, &plain).await.is_err());
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_time_limited() {
+        let db = memory_db().await;
+        let (url, received) = listen(vec![500; 10]).await;
+        let options = WebhookOptions {
+            secret: None,
+            retries: MAX_RETRIES,
+            allow_private: true,
+        };
+        let target = add_webhook_target(&db, "test_1", &url, &options)
+            .await
+            .unwrap();
+        let notifier = WebhookNotifier::new()
+            .backoff(Duration::from_millis(100))
+            .retry_time(Duration::from_millis(250));
+
+        let plain = Notification::new("test_1", "text".to_string());
+        assert!(notifier.notify(&target, &plain).await.is_err());
+        // retried after 100ms, the next retry after 200ms more would pass the retry time
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+}
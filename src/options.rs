@@ -0,0 +1,133 @@
+use crate::cli::Cli;
+
+/// Options of [`crate::ChinaUnicomHandler`]
+#[derive(Clone, Debug)]
+pub struct HandlerOptions {
+    /// admin users, in the form of `{server}_{user_id}`, the same as the user in config
+    pub admins: Vec<String>,
+    /// allow the commands safe for groups to be used in groups, and groups to be subscribed
+    pub groups: bool,
+    /// the command prefix, default to `/china_unicom`
+    pub prefix: String,
+    /// other prefixes also routed to this handler, e.g. `/联通`
+    pub aliases: Vec<String>,
+    /// the smtp server to send emails, email targets can't be added if not set
+    #[cfg(feature = "email")]
+    pub smtp: Option<crate::notifier::email::SmtpOptions>,
+    /// the mqtt broker to publish the usage of every query to
+    #[cfg(feature = "mqtt")]
+    pub mqtt: Option<crate::publisher::mqtt::MqttOptions>,
+    /// the endpoint of the prometheus metrics
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<crate::metrics::MetricsOptions>,
+    /// the address of the http api
+    #[cfg(feature = "api")]
+    pub api: Option<crate::api::ApiOptions>,
+}
+
+impl Default for HandlerOptions {
+    fn default() -> Self {
+        Self {
+            admins: Vec::new(),
+            groups: false,
+            prefix: Cli::name().to_string(),
+            aliases: Vec::new(),
+            #[cfg(feature = "email")]
+            smtp: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "api")]
+            api: None,
+        }
+    }
+}
+
+impl HandlerOptions {
+    pub fn admin(mut self, user: impl Into<String>) -> Self {
+        self.admins.push(user.into());
+        self
+    }
+
+    pub fn allow_groups(mut self) -> Self {
+        self.groups = true;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    #[cfg(feature = "email")]
+    pub fn smtp(mut self, smtp: crate::notifier::email::SmtpOptions) -> Self {
+        self.smtp = Some(smtp);
+        self
+    }
+
+    #[cfg(feature = "mqtt")]
+    pub fn mqtt(mut self, mqtt: crate::publisher::mqtt::MqttOptions) -> Self {
+        self.mqtt = Some(mqtt);
+        self
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn metrics(mut self, metrics: crate::metrics::MetricsOptions) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    #[cfg(feature = "api")]
+    pub fn api(mut self, api: crate::api::ApiOptions) -> Self {
+        self.api = Some(api);
+        self
+    }
+
+    pub fn is_admin(&self, user: &str) -> bool {
+        self.admins.iter().any(|admin| admin == user)
+    }
+
+    /// match the prefix or an alias at the start of the text, followed by whitespace or the end,
+    /// return the matched prefix and the rest of the text
+    pub fn match_prefix<'a>(&'a self, text: &'a str) -> Option<(&'a str, &'a str)> {
+        let text = text.trim_start();
+        std::iter::once(&self.prefix)
+            .chain(self.aliases.iter())
+            .filter(|prefix| !prefix.is_empty())
+            .find_map(|prefix| {
+                let rest = text.strip_prefix(prefix.as_str())?;
+                (rest.is_empty() || rest.starts_with(char::is_whitespace))
+                    .then_some((prefix.as_str(), rest))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HandlerOptions;
+
+    #[test]
+    fn match_prefix() {
+        let options = HandlerOptions::default().alias("/cu").alias("/联通");
+        assert_eq!(
+            options.match_prefix("/china_unicom query"),
+            Some(("/china_unicom", " query"))
+        );
+        assert_eq!(options.match_prefix("/cu"), Some(("/cu", "")));
+        assert_eq!(
+            options.match_prefix("/联通 query"),
+            Some(("/联通", " query"))
+        );
+        assert_eq!(options.match_prefix("/china_unicomfoo query"), None);
+        assert_eq!(options.match_prefix("/cux"), None);
+
+        let options = HandlerOptions::default().prefix("/cu");
+        assert_eq!(options.match_prefix("/china_unicom query"), None);
+    }
+}
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use china_unicom_rs::{data::ChinaUnicomData, online::online, query::query_china_unicom_data};
+
+use super::{ErrorKind, RefreshedCredentials, UsageProvider};
+
+/// the default provider, query the China Unicom app api
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChinaUnicomProvider;
+
+#[async_trait]
+impl UsageProvider for ChinaUnicomProvider {
+    async fn fetch_usage(&self, cookie: &str) -> anyhow::Result<ChinaUnicomData> {
+        query_china_unicom_data(cookie).await
+    }
+
+    async fn refresh_credentials(
+        &self,
+        token_online: &str,
+        app_id: &str,
+    ) -> anyhow::Result<RefreshedCredentials> {
+        let resp = online(token_online, app_id).await?;
+        Ok(RefreshedCredentials {
+            cookie: resp.cookie,
+            token_online: resp.online_token,
+        })
+    }
+
+    fn classify_error(&self, error: &anyhow::Error) -> ErrorKind {
+        // the api returns 999998 when the cookie is expired
+        if format!("{:?}", error).contains("999998") {
+            ErrorKind::AuthExpired
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChinaUnicomProvider, ErrorKind, UsageProvider};
+
+    #[test]
+    fn classify() {
+        let provider = ChinaUnicomProvider;
+        let expired = anyhow::anyhow!("query failed: {{\"code\":\"999998\",\"desc\":\"login\"}}");
+        assert_eq!(provider.classify_error(&expired), ErrorKind::AuthExpired);
+        // the code may be wrapped in a context
+        let expired = expired.context("fetch usage");
+        assert_eq!(provider.classify_error(&expired), ErrorKind::AuthExpired);
+        let other = anyhow::anyhow!("connection refused");
+        assert_eq!(provider.classify_error(&other), ErrorKind::Other);
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use china_unicom_rs::data::ChinaUnicomData;
+
+pub mod china_unicom;
+pub use china_unicom::ChinaUnicomProvider;
+
+/// the kind of an error of a provider
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// the cookie is expired, it can be refreshed by [`UsageProvider::refresh_credentials`]
+    AuthExpired,
+    Other,
+}
+
+/// the credentials refreshed by the provider
+#[derive(Clone, Debug)]
+pub struct RefreshedCredentials {
+    pub cookie: String,
+    pub token_online: String,
+}
+
+/// the source of the usage data
+#[async_trait]
+pub trait UsageProvider: Send + Sync {
+    /// fetch the usage data with the cookie
+    async fn fetch_usage(&self, cookie: &str) -> anyhow::Result<ChinaUnicomData>;
+
+    /// get a new cookie with the long-lived credentials
+    async fn refresh_credentials(
+        &self,
+        token_online: &str,
+        app_id: &str,
+    ) -> anyhow::Result<RefreshedCredentials>;
+
+    /// classify an error returned by [`UsageProvider::fetch_usage`]
+    fn classify_error(&self, error: &anyhow::Error) -> ErrorKind;
+}
+
+pub type SharedProvider = Arc<dyn UsageProvider>;
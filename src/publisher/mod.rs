@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::notifier::UsageEvent;
+
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "mqtt")]
+pub use mqtt::MqttPublisher;
+
+/// receive the usage of every successful query, whether notified or not
+#[async_trait]
+pub trait UsagePublisher: Send + Sync {
+    async fn publish(&self, user: &str, event: &UsageEvent) -> anyhow::Result<()>;
+}
+
+/// all the publishers
+#[derive(Clone, Default)]
+pub struct Publishers(Vec<Arc<dyn UsagePublisher>>);
+
+impl Publishers {
+    pub fn with(mut self, publisher: impl UsagePublisher + 'static) -> Self {
+        self.0.push(Arc::new(publisher));
+        self
+    }
+
+    /// publish to all, the failures are only logged as the query itself succeeded
+    pub async fn publish(&self, user: &str, event: &UsageEvent) {
+        for publisher in &self.0 {
+            if let Err(e) = publisher.publish(user, event).await {
+                tracing::error!("ChinaUnicom: Publish usage of user {} failed: {}", user, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use dashmap::DashSet;
+use rumqttc::{AsyncClient, Event, Packet, QoS};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::notifier::{UsageEvent, UsageFields};
+
+use super::UsagePublisher;
+
+/// the sensors announced to home assistant, as `(key in the state, name, unit)`
+const SENSORS: [(&str, &str, &str); 7] = [
+    ("non_limit_flow_left", "Remaining general flow", "GB"),
+    ("limit_flow_left", "Remaining directed flow", "GB"),
+    ("non_free_flow_used_today", "Non-free flow used today", "GB"),
+    ("free_flow_used_today", "Free flow used today", "GB"),
+    ("sum_flow_used", "Flow used", "GB"),
+    ("non_free_flow_used", "Non-free flow used", "GB"),
+    ("sum_voice_used", "Voice used", "min"),
+];
+
+/// the mqtt broker to publish the usage, set by the admin
+#[derive(Clone, Debug)]
+pub struct MqttOptions {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// the state of a user is published to `{topic}/{user}/state`
+    pub topic: String,
+    /// the prefix of the home assistant discovery topics, `None` to not announce the sensors
+    pub discovery_prefix: Option<String>,
+}
+
+impl MqttOptions {
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port: 1883,
+            client_id: "china_unicom_oxidebot".to_string(),
+            username: None,
+            password: None,
+            topic: "china_unicom".to_string(),
+            discovery_prefix: Some("homeassistant".to_string()),
+        }
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = client_id.into();
+        self
+    }
+
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = topic.into();
+        self
+    }
+
+    pub fn discovery_prefix(mut self, discovery_prefix: Option<String>) -> Self {
+        self.discovery_prefix = discovery_prefix;
+        self
+    }
+
+    pub fn state_topic(&self, user: &str) -> String {
+        format!("{}/{}/state", self.topic, topic_id(user))
+    }
+
+    /// the retained discovery configs of the sensors of the user, as `(topic, payload)`
+    pub fn discovery_messages(&self, user: &str) -> Vec<(String, String)> {
+        let Some(prefix) = &self.discovery_prefix else {
+            return Vec::new();
+        };
+        let id = topic_id(user);
+        SENSORS
+            .iter()
+            .map(|(key, name, unit)| {
+                let payload = json!({
+                    "name": name,
+                    "unique_id": format!("china_unicom_{}_{}", id, key),
+                    "state_topic": self.state_topic(user),
+                    "value_template": format!("{{{{ value_json.{} }}}}", key),
+                    "unit_of_measurement": unit,
+                    "device": {
+                        "identifiers": [format!("china_unicom_{}", id)],
+                        "name": format!("ChinaUnicom {}", user),
+                    },
+                });
+                (
+                    format!("{}/sensor/china_unicom_{}/{}/config", prefix, id, key),
+                    payload.to_string(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// the user in a topic, the characters other than letters, digits, `_` and `-` are replaced
+fn topic_id(user: &str) -> String {
+    user.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// the state published of a user
+#[derive(Clone, Debug, Serialize)]
+pub struct UsageState {
+    pub package_name: String,
+    pub non_limit_flow_left: f64,
+    pub limit_flow_left: f64,
+    /// 0 before the first query of the day is recorded
+    pub non_free_flow_used_today: f64,
+    pub free_flow_used_today: f64,
+    #[serde(flatten)]
+    pub fields: UsageFields,
+}
+
+impl From<&UsageEvent> for UsageState {
+    fn from(event: &UsageEvent) -> Self {
+        let data = &event.data;
+        let (non_free_today, free_today) = match &event.daily {
+            Some(daily) if daily.time.date_naive() == data.time.date_naive() => (
+                data.non_free_flow_used - daily.non_free_flow_used,
+                data.free_flow_used - daily.free_flow_used,
+            ),
+            _ => (0.0, 0.0),
+        };
+        Self {
+            package_name: data.package_name.clone(),
+            non_limit_flow_left: data.non_limit_flow - data.non_limit_flow_used,
+            limit_flow_left: data.limit_flow - data.limit_flow_used,
+            non_free_flow_used_today: non_free_today,
+            free_flow_used_today: free_today,
+            fields: UsageFields::from(data),
+        }
+    }
+}
+
+/// publish the usage to a mqtt broker, the sensors of a user are announced at the first publish
+/// and again after reconnected, as a restarted broker may have lost the retained configs
+pub struct MqttPublisher {
+    options: MqttOptions,
+    client: AsyncClient,
+    announced: Arc<DashSet<String>>,
+}
+
+impl MqttPublisher {
+    /// connect in the background, must be called in a tokio runtime
+    pub fn new(options: MqttOptions) -> Self {
+        let mut mqtt_options =
+            rumqttc::MqttOptions::new(&options.client_id, &options.host, options.port);
+        mqtt_options.set_keep_alive(Duration::from_secs(30));
+        if let (Some(username), Some(password)) = (&options.username, &options.password) {
+            mqtt_options.set_credentials(username, password);
+        }
+        let (client, mut event_loop) = AsyncClient::new(mqtt_options, 64);
+        let announced = Arc::new(DashSet::new());
+        let announced_clone = announced.clone();
+        tokio::spawn(async move {
+            // the messages queued before the first connection are sent on it
+            let mut connected = false;
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        if connected {
+                            announced_clone.clear();
+                        }
+                        connected = true;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::error!("ChinaUnicom: Mqtt connection error: {}", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+        Self {
+            options,
+            client,
+            announced,
+        }
+    }
+}
+
+#[async_trait]
+impl UsagePublisher for MqttPublisher {
+    /// only queue the messages, so a broker down never blocks the query, the messages are
+    /// dropped when the queue is full
+    async fn publish(&self, user: &str, event: &UsageEvent) -> anyhow::Result<()> {
+        if !self.announced.contains(user) {
+            for (topic, payload) in self.options.discovery_messages(user) {
+                self.client
+                    .try_publish(topic, QoS::AtLeastOnce, true, payload)?;
+            }
+            self.announced.insert(user.to_string());
+        }
+        let state = serde_json::to_string(&UsageState::from(event))?;
+        self.client.try_publish(
+            self.options.state_topic(user),
+            QoS::AtLeastOnce,
+            true,
+            state,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use serde_json::Value;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::{MqttOptions, MqttPublisher, SENSORS};
+    use crate::{
+        model::DailyModel,
+        notifier::UsageEvent,
+        publisher::UsagePublisher,
+        testing::{at, usage},
+    };
+
+    /// a published message, as `(topic, retain, payload)`
+    type Published = (String, bool, Vec<u8>);
+
+    /// a local mqtt broker stand-in, acknowledging the connects and publishes, the first
+    /// connection is closed after `close_after` publishes, as if the broker restarted
+    async fn listen(
+        close_after: Option<usize>,
+    ) -> (u16, Arc<Mutex<Vec<Published>>>, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let published = Arc::new(Mutex::new(Vec::new()));
+        let published_clone = published.clone();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let connections_clone = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let first = connections_clone.fetch_add(1, Ordering::SeqCst) == 0;
+                let mut count = 0;
+                while let Ok(header) = stream.read_u8().await {
+                    // the remaining length is a variable byte integer
+                    let mut length = 0usize;
+                    let mut shift = 0;
+                    loop {
+                        let byte = stream.read_u8().await.unwrap();
+                        length |= ((byte & 0x7f) as usize) << shift;
+                        shift += 7;
+                        if byte & 0x80 == 0 {
+                            break;
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    stream.read_exact(&mut body).await.unwrap();
+                    match header >> 4 {
+                        1 => stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap(),
+                        3 => {
+                            let qos = (header >> 1) & 0x03;
+                            let topic_length = u16::from_be_bytes([body[0], body[1]]) as usize;
+                            let topic =
+                                String::from_utf8(body[2..2 + topic_length].to_vec()).unwrap();
+                            let mut offset = 2 + topic_length;
+                            if qos > 0 {
+                                let id = [body[offset], body[offset + 1]];
+                                offset += 2;
+                                stream.write_all(&[0x40, 0x02, id[0], id[1]]).await.unwrap();
+                            }
+                            published_clone.lock().unwrap().push((
+                                topic,
+                                header & 0x01 == 1,
+                                body[offset..].to_vec(),
+                            ));
+                            count += 1;
+                            if first && close_after == Some(count) {
+                                break;
+                            }
+                        }
+                        12 => stream.write_all(&[0xd0, 0x00]).await.unwrap(),
+                        14 => break,
+                        _ => {}
+                    }
+                }
+            }
+        });
+        (port, published, connections)
+    }
+
+    fn usage_event() -> UsageEvent {
+        let daily = DailyModel {
+            time: at(1, 0, 5),
+            non_free_flow_used: 1.0,
+            ..Default::default()
+        };
+        let mut data = usage(at(1, 9, 0), 1.5);
+        data.non_limit_flow = 10.0;
+        data.non_limit_flow_used = 4.0;
+        UsageEvent {
+            data,
+            last: None,
+            daily: Some(daily),
+            fired: Vec::new(),
+        }
+    }
+
+    /// wait until the stand-in received `expected` messages
+    async fn wait_published(published: &Mutex<Vec<Published>>, expected: usize) {
+        for _ in 0..100 {
+            if published.lock().unwrap().len() >= expected {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn publish_with_discovery() {
+        let (port, published, _) = listen(None).await;
+        let options = MqttOptions::new("127.0.0.1")
+            .port(port)
+            .topic("home/china_unicom");
+        let publisher = MqttPublisher::new(options);
+
+        let event = usage_event();
+        publisher.publish("qq_123", &event).await.unwrap();
+        publisher.publish("qq_123", &event).await.unwrap();
+
+        let expected = SENSORS.len() + 2;
+        wait_published(&published, expected).await;
+        let published = published.lock().unwrap();
+        assert_eq!(published.len(), expected);
+        assert!(published.iter().all(|(_, retain, _)| *retain));
+
+        // the sensors are announced only once
+        let (topic, _, payload) = &published[0];
+        assert_eq!(
+            topic,
+            "homeassistant/sensor/china_unicom_qq_123/non_limit_flow_left/config"
+        );
+        let config: Value = serde_json::from_slice(payload).unwrap();
+        assert_eq!(config["state_topic"], "home/china_unicom/qq_123/state");
+        assert_eq!(
+            config["value_template"],
+            "{{ value_json.non_limit_flow_left }}"
+        );
+
+        let (topic, _, payload) = &published[expected - 1];
+        assert_eq!(topic, "home/china_unicom/qq_123/state");
+        let state: Value = serde_json::from_slice(payload).unwrap();
+        assert_eq!(state["non_limit_flow_left"], 6.0);
+        assert_eq!(state["non_free_flow_used_today"], 0.5);
+        assert_eq!(state["package_name"], "test package");
+        assert_eq!(state["sum_flow_used"], 1.5);
+    }
+
+    #[tokio::test]
+    async fn announce_after_reconnect() {
+        let (port, published, connections) = listen(Some(SENSORS.len() + 1)).await;
+        let publisher = MqttPublisher::new(MqttOptions::new("127.0.0.1").port(port));
+        let event = usage_event();
+        publisher.publish("qq_123", &event).await.unwrap();
+        wait_published(&published, SENSORS.len() + 1).await;
+
+        // the connection is retried 5s after closed
+        for _ in 0..100 {
+            if connections.load(Ordering::SeqCst) >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        publisher.publish("qq_123", &event).await.unwrap();
+        let expected = 2 * (SENSORS.len() + 1);
+        wait_published(&published, expected).await;
+        let published = published.lock().unwrap();
+        assert_eq!(published.len(), expected);
+        assert_eq!(published[SENSORS.len() + 1].0, published[0].0);
+    }
+
+    #[tokio::test]
+    async fn broker_down() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let publisher = MqttPublisher::new(MqttOptions::new("127.0.0.1").port(port));
+        let event = usage_event();
+        // more messages than the queue holds, the publish fails instead of waiting
+        let result = tokio::time::timeout(Duration::from_secs(1), async {
+            for _ in 0..100 {
+                if publisher.publish("qq_123", &event).await.is_err() {
+                    return true;
+                }
+            }
+            false
+        })
+        .await;
+        assert_eq!(result, Ok(true));
+    }
+}
@@ -0,0 +1,350 @@
+//! the flows of the `china_unicom` binary, tracking the usage without a chat bot
+
+use std::process::Stdio;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{Local, NaiveDate};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder,
+};
+use tokio::{io::AsyncWriteExt, process::Command, task::JoinHandle};
+
+use crate::{
+    model::{
+        config, target, target::ensure_private_target, ConfigActiveModel, ConfigEntity,
+        ConfigModel, TargetEntity,
+    },
+    provider::UsageProvider,
+    publisher::Publishers,
+    utils::{
+        china_unicom::{
+            create_china_unicom_task, query_dry_run, query_once, verify_credentials, TaskContext,
+        },
+        credential::{normalize_cookie, Credentials},
+        history::{bucket_deltas, format_history_page, query_history, Granularity},
+        sink::MessageSink,
+    },
+};
+
+/// the bot of the users registered by the command line
+pub const CLI_BOT: &str = "cli";
+
+/// print the messages to stdout, or pipe them to a shell command with the user in
+/// `CHINA_UNICOM_USER`
+pub struct CommandSink {
+    pub exec: Option<String>,
+}
+
+impl CommandSink {
+    async fn deliver(&self, user: &str, message: String) -> Result<()> {
+        let Some(exec) = &self.exec else {
+            println!(
+                "[{}] {}:\n{}\n",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                user,
+                message
+            );
+            return Ok(());
+        };
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(exec)
+            .env("CHINA_UNICOM_USER", user)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(message.as_bytes()).await?;
+        }
+        let status = child.wait().await?;
+        if !status.success() {
+            anyhow::bail!("`{}` exited with {}", exec, status);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageSink for CommandSink {
+    async fn send_private(&self, user: &str, _bot: &str, message: String) -> Result<()> {
+        self.deliver(user, message).await
+    }
+
+    async fn send_group(&self, group: &str, _bot: &str, message: String) -> Result<()> {
+        self.deliver(group, message).await
+    }
+}
+
+/// get the config of the user, if not registered, return an error telling to register
+pub async fn user_config(db: &sea_orm::DatabaseConnection, user: &str) -> Result<ConfigModel> {
+    ConfigEntity::find_by_id(user)
+        .one(db)
+        .await?
+        .ok_or(anyhow::anyhow!(
+            "User {} is not registered, use the `register` command first.",
+            user
+        ))
+}
+
+/// register the user after a test query, return the message to print
+pub async fn register(
+    db: &sea_orm::DatabaseConnection,
+    provider: &dyn UsageProvider,
+    user: &str,
+    mut credentials: Credentials,
+) -> Result<String> {
+    let mut config = ConfigModel {
+        user: user.to_string(),
+        bot: CLI_BOT.to_string(),
+        ..Default::default()
+    };
+    credentials.cookie = credentials.cookie.map(|cookie| normalize_cookie(&cookie));
+    credentials.apply(&mut config);
+    if let Err(errors) = config.validate() {
+        anyhow::bail!("Register failed:\n{}", errors.join("\n"));
+    }
+
+    let (config, data) = verify_credentials(provider, config)
+        .await
+        .map_err(|e| anyhow::anyhow!("Register failed, nothing is saved.\n{}", e))?;
+    let config_active: ConfigActiveModel = config.into();
+    match ConfigEntity::insert(config_active).exec(db).await {
+        Ok(_) => {
+            ensure_private_target(db, user, CLI_BOT).await?;
+            Ok(format!(
+                "Register success, your package: {}.\nUse the `daemon` command to run the task.",
+                data.package_name
+            ))
+        }
+        Err(sea_orm::DbErr::RecordNotInserted) => {
+            anyhow::bail!("User {} has already registered.", user)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// query the usage of the user now, the dry run only shows which thresholds would fire
+pub async fn query(
+    db: &sea_orm::DatabaseConnection,
+    provider: &dyn UsageProvider,
+    user: &str,
+    dry_run: bool,
+) -> Result<String> {
+    let config = user_config(db, user).await?;
+    if dry_run {
+        return query_dry_run(db, provider, &config).await;
+    }
+    let (_should_send, notification) =
+        query_once(db, provider, &Publishers::default(), &config).await?;
+    Ok(notification.text)
+}
+
+/// the usage of the user per hour or day from `from` to `to`
+pub async fn report(
+    db: &sea_orm::DatabaseConnection,
+    user: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    granularity: Granularity,
+    page: usize,
+) -> Result<String> {
+    user_config(db, user).await?;
+    if from > to {
+        anyhow::bail!("The `--from` date must not be after the `--to` date.");
+    }
+    let (base, readings) = query_history(db, user, from, to).await?;
+    let buckets = bucket_deltas(base.as_ref(), &readings, granularity);
+    Ok(format!(
+        "Usage from {} to {}:\n{}",
+        from,
+        to,
+        format_history_page(&buckets, page)
+    ))
+}
+
+/// the targets of the user, one per line
+pub async fn list_targets(db: &sea_orm::DatabaseConnection, user: &str) -> Result<String> {
+    let targets = TargetEntity::find()
+        .filter(target::Column::User.eq(user))
+        .order_by_asc(target::Column::Id)
+        .all(db)
+        .await?;
+    if targets.is_empty() {
+        return Ok("No target, notifications are not delivered.".to_string());
+    }
+    let lines: Vec<String> = targets.iter().map(|target| target.describe()).collect();
+    Ok(lines.join("\n"))
+}
+
+/// the configs of the users of the chat bot, the users registered by the command line are
+/// left to the `daemon` command
+pub async fn bot_configs(db: &sea_orm::DatabaseConnection) -> Result<Vec<ConfigModel>> {
+    Ok(ConfigEntity::find()
+        .filter(config::Column::Bot.ne(CLI_BOT))
+        .all(db)
+        .await?)
+}
+
+/// start the tasks of the users registered by the command line with the task enabled,
+/// the users of the chat bot sharing the database are left to the bot
+pub async fn start_tasks(context: TaskContext) -> Result<Vec<JoinHandle<()>>> {
+    let configs = ConfigEntity::find()
+        .filter(config::Column::Bot.eq(CLI_BOT))
+        .filter(config::Column::EnableTask.eq(true))
+        .all(&context.db)
+        .await?;
+    let mut tasks = Vec::new();
+    for mut config in configs {
+        let repaired = config.repair();
+        if !repaired.is_empty() {
+            eprintln!(
+                "Repaired invalid config of user {}: {}",
+                config.user,
+                repaired.join(", ")
+            );
+            let config_active = config.clone().into_active_model().reset_all();
+            ConfigEntity::update(config_active)
+                .exec(&context.db)
+                .await?;
+        }
+        match create_china_unicom_task(context.clone(), config.user.clone()).await {
+            Ok(task) => tasks.push(task),
+            Err(e) => eprintln!("Task of {} start failed: {:?}", config.user, e),
+        }
+    }
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, Set};
+
+    use super::{bot_configs, list_targets, query, register, start_tasks, CommandSink, CLI_BOT};
+    use crate::{
+        model::ConfigEntity,
+        testing::{at, fake_context, insert_config, usage},
+        utils::{credential::Credentials, sink::MessageSink},
+    };
+
+    fn credentials() -> Credentials {
+        Credentials {
+            cookie: Some("Cookie: a=1; b=2".to_string()),
+            app_id: Some("app_id".to_string()),
+            token_online: Some("token_online".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn register_and_query() {
+        let (context, provider, _sink) = fake_context().await;
+        let db = &context.db;
+        assert!(query(db, provider.as_ref(), "cli", false).await.is_err());
+
+        provider.push_error("network error");
+        assert!(register(db, provider.as_ref(), "cli", credentials())
+            .await
+            .is_err());
+        assert!(ConfigEntity::find_by_id("cli")
+            .one(db)
+            .await
+            .unwrap()
+            .is_none());
+
+        provider.push_usage(usage(at(1, 8, 0), 1.0));
+        let message = register(db, provider.as_ref(), "cli", credentials())
+            .await
+            .unwrap();
+        assert!(message.contains("test package"));
+        let config = ConfigEntity::find_by_id("cli")
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.bot, CLI_BOT);
+        assert_eq!(config.cookie, "a=1; b=2");
+        assert!(list_targets(db, "cli").await.unwrap().contains("private"));
+        provider.push_usage(usage(at(1, 8, 0), 1.0));
+        assert!(register(db, provider.as_ref(), "cli", credentials())
+            .await
+            .is_err());
+
+        provider
+            .push_usage(usage(at(1, 9, 0), 1.5))
+            .push_usage(usage(at(1, 10, 0), 2.0));
+        let text = query(db, provider.as_ref(), "cli", true).await.unwrap();
+        assert!(text.contains("test package"));
+        let text = query(db, provider.as_ref(), "cli", false).await.unwrap();
+        assert!(!text.is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_cli_tasks() {
+        let (context, provider, sink) = fake_context().await;
+        provider.push_usage(usage(at(1, 8, 0), 1.0));
+        register(&context.db, provider.as_ref(), "cli", credentials())
+            .await
+            .unwrap();
+        insert_config(&context.db, "bot_user").await;
+        // an interval out of bounds, as if written by an older version
+        let config = ConfigEntity::find_by_id("cli")
+            .one(&context.db)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut config_active = config.into_active_model();
+        config_active.interval = Set(1);
+        config_active.update(&context.db).await.unwrap();
+
+        provider.push_usage(usage(at(1, 9, 0), 1.5));
+        let tasks = start_tasks(context.clone()).await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        for task in tasks {
+            task.abort();
+        }
+        let messages = sink.take();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, "private:cli");
+        let config = ConfigEntity::find_by_id("cli")
+            .one(&context.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.interval, 60);
+
+        // the chat bot leaves the user of the command line to the daemon
+        let configs = bot_configs(&context.db).await.unwrap();
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].user, "bot_user");
+    }
+
+    #[tokio::test]
+    async fn command_sink() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!("china_unicom_sink_{}", nanos));
+        let sink = CommandSink {
+            exec: Some(format!(
+                "printf '%s:' \"$CHINA_UNICOM_USER\" > '{}' && cat >> '{}'",
+                path.display(),
+                path.display()
+            )),
+        };
+        sink.send_private("cli", CLI_BOT, "used 1.5 GB".to_string())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "cli:used 1.5 GB");
+        std::fs::remove_file(&path).unwrap();
+
+        let sink = CommandSink {
+            exec: Some("exit 3".to_string()),
+        };
+        assert!(sink
+            .send_private("cli", CLI_BOT, "message".to_string())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! fakes to run the flows offline in tests
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use china_unicom_rs::data::ChinaUnicomData;
+use chrono::{DateTime, Local, TimeZone};
+use sea_orm::EntityTrait;
+
+use crate::{
+    model::{target::ensure_private_target, ConfigActiveModel, ConfigEntity, ConfigModel},
+    notifier::{ChatNotifier, Notifiers},
+    provider::{ErrorKind, RefreshedCredentials, UsageProvider},
+    publisher::Publishers,
+    utils::{
+        china_unicom::TaskContext, db::connect_db, failure::FailureTracker, sink::MessageSink,
+    },
+};
+
+const AUTH_EXPIRED: &str = "scripted: auth expired";
+
+/// a new empty in-memory database
+pub async fn memory_db() -> sea_orm::DatabaseConnection {
+    connect_db("sqlite::memory:").await.unwrap()
+}
+
+/// a valid config of the user with the private target, inserted into the database
+pub async fn insert_config(db: &sea_orm::DatabaseConnection, user: &str) -> ConfigModel {
+    let config = ConfigModel {
+        user: user.to_string(),
+        bot: "test_bot".to_string(),
+        cookie: "cookie".to_string(),
+        app_id: "app_id".to_string(),
+        token_online: "token_online".to_string(),
+        ..Default::default()
+    };
+    let config_active: ConfigActiveModel = config.clone().into();
+    ConfigEntity::insert(config_active).exec(db).await.unwrap();
+    ensure_private_target(db, user, &config.bot).await.unwrap();
+    config
+}
+
+/// a local time on 2026-10-`day`
+pub fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Local
+        .with_ymd_and_hms(2026, 10, day, hour, minute, 0)
+        .unwrap()
+}
+
+/// the usage data at the time, with `non_free` GB non-free flow used
+pub fn usage(time: DateTime<Local>, non_free: f64) -> ChinaUnicomData {
+    ChinaUnicomData {
+        package_name: "test package".to_string(),
+        time,
+        sum_flow_used: non_free,
+        non_free_flow_used: non_free,
+        ..Default::default()
+    }
+}
+
+/// a provider returning the scripted results in order
+#[derive(Default)]
+pub struct ScriptedProvider {
+    script: Mutex<VecDeque<Result<ChinaUnicomData, String>>>,
+    /// the cookies used to fetch
+    pub cookies: Mutex<Vec<String>>,
+    /// the count of refreshes
+    pub refreshes: AtomicUsize,
+    /// refreshing fails if set
+    pub refresh_error: Mutex<Option<String>>,
+}
+
+impl ScriptedProvider {
+    pub fn push_usage(&self, data: ChinaUnicomData) -> &Self {
+        self.script.lock().unwrap().push_back(Ok(data));
+        self
+    }
+
+    pub fn push_auth_expired(&self) -> &Self {
+        self.script
+            .lock()
+            .unwrap()
+            .push_back(Err(AUTH_EXPIRED.to_string()));
+        self
+    }
+
+    pub fn push_error(&self, error: &str) -> &Self {
+        self.script
+            .lock()
+            .unwrap()
+            .push_back(Err(error.to_string()));
+        self
+    }
+}
+
+#[async_trait]
+impl UsageProvider for ScriptedProvider {
+    async fn fetch_usage(&self, cookie: &str) -> anyhow::Result<ChinaUnicomData> {
+        self.cookies.lock().unwrap().push(cookie.to_string());
+        match self.script.lock().unwrap().pop_front() {
+            Some(Ok(data)) => Ok(data),
+            Some(Err(e)) => Err(anyhow::anyhow!(e)),
+            None => Err(anyhow::anyhow!("scripted: no more results")),
+        }
+    }
+
+    async fn refresh_credentials(
+        &self,
+        _token_online: &str,
+        _app_id: &str,
+    ) -> anyhow::Result<RefreshedCredentials> {
+        if let Some(e) = self.refresh_error.lock().unwrap().clone() {
+            return Err(anyhow::anyhow!(e));
+        }
+        let count = self.refreshes.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(RefreshedCredentials {
+            cookie: format!("refreshed_cookie_{}", count),
+            token_online: format!("refreshed_token_{}", count),
+        })
+    }
+
+    fn classify_error(&self, error: &anyhow::Error) -> ErrorKind {
+        if error.to_string() == AUTH_EXPIRED {
+            ErrorKind::AuthExpired
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+/// a sink recording the messages as `(target, message)`, the target is `private:{user}`
+/// or `group:{group}`
+#[derive(Default)]
+pub struct RecordingSink {
+    messages: Mutex<Vec<(String, String)>>,
+    /// sending to groups fails if set
+    pub fail_groups: AtomicBool,
+}
+
+impl RecordingSink {
+    /// take the messages recorded so far
+    pub fn take(&self) -> Vec<(String, String)> {
+        std::mem::take(&mut *self.messages.lock().unwrap())
+    }
+}
+
+#[async_trait]
+impl MessageSink for RecordingSink {
+    async fn send_private(&self, user: &str, _bot: &str, message: String) -> anyhow::Result<()> {
+        self.messages
+            .lock()
+            .unwrap()
+            .push((format!("private:{}", user), message));
+        Ok(())
+    }
+
+    async fn send_group(&self, group: &str, _bot: &str, message: String) -> anyhow::Result<()> {
+        if self.fail_groups.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("recording: group unavailable"));
+        }
+        self.messages
+            .lock()
+            .unwrap()
+            .push((format!("group:{}", group), message));
+        Ok(())
+    }
+}
+
+/// a task context with the fakes, the fakes are returned to script and inspect them
+pub async fn fake_context() -> (TaskContext, Arc<ScriptedProvider>, Arc<RecordingSink>) {
+    let provider = Arc::new(ScriptedProvider::default());
+    let sink = Arc::new(RecordingSink::default());
+    let context = TaskContext {
+        db: memory_db().await,
+        provider: provider.clone(),
+        notifiers: Notifiers::default().with(ChatNotifier::new(sink.clone())),
+        publishers: Publishers::default(),
+        failures: FailureTracker::default(),
+    };
+    (context, provider, sink)
+}
@@ -1,15 +1,23 @@
 use anyhow::Result;
-use china_unicom_rs::{data::ChinaUnicomData, online::online, query::query_china_unicom_data};
+use china_unicom_rs::data::ChinaUnicomData;
 use chrono::TimeDelta;
 use sea_orm::{ActiveModelTrait, EntityTrait, Set};
 use tokio::{task::JoinHandle, time::sleep};
 
 use crate::model::{
-    daily::build_daily_active, last::build_last_active, ConfigActiveModel, ConfigEntity,
-    ConfigModel, DailyActiveModel, DailyEntity, DailyModel, LastActiveModel, LastEntity, LastModel,
+    config::MIN_INTERVAL, daily::build_daily_active, last::build_last_active, ConfigActiveModel,
+    ConfigEntity, ConfigModel, DailyActiveModel, DailyEntity, DailyModel, LastActiveModel,
+    LastEntity, LastModel,
 };
+use crate::notifier::{Notification, Notifiers, UsageEvent};
+use crate::provider::{ErrorKind, SharedProvider, UsageProvider};
+use crate::publisher::Publishers;
 
-use super::oxidebot_util::send_message;
+use super::{
+    failure::FailureTracker,
+    history::record_history,
+    units::{HumanDuration, HumanSize},
+};
 
 const FORMAT_LAST: &'static str = "[区间时长] 跳: [区间流量收费用量], 免: [区间流量免费用量]";
 
@@ -19,36 +27,69 @@ const FORMAT_LEFT: &'static str = "通用余: [流量通用余量], 定向余: [
 
 const FORMAT_USED: &'static str = "通用已用: [流量通用用量], 定向已用: [流量定向用量]";
 
-pub async fn query_once(
+async fn fetch_data(
     db: &sea_orm::DatabaseConnection,
+    provider: &dyn UsageProvider,
     config: &ConfigModel,
-) -> anyhow::Result<(bool, String)> {
+) -> anyhow::Result<ChinaUnicomData> {
     // when the cookie is expired, we need to update the cookie
-    let new_data = match query_china_unicom_data(&config.cookie).await {
-        Ok(data) => data,
-        Err(e) => {
-            let error_str = format!("{:?}", e);
-            if error_str.contains("999998") {
-                let new_config = handle_auth_update(config, db).await?;
+    match provider.fetch_usage(&config.cookie).await {
+        Ok(data) => Ok(data),
+        Err(e) => match provider.classify_error(&e) {
+            ErrorKind::AuthExpired => {
+                let new_config = handle_auth_update(config, provider, db).await?;
                 tracing::info!("Update auth info for user: {}", new_config.user);
-                let data = query_china_unicom_data(&new_config.cookie).await?;
-                data
-            } else {
-                return Err(e);
+                provider.fetch_usage(&new_config.cookie).await
             }
-        }
-    };
+            ErrorKind::Other => Err(e),
+        },
+    }
+}
+
+/// query, record and publish the data, return whether the last data is updated
+/// (a notification should be sent) and the notification of the data
+pub async fn query_once(
+    db: &sea_orm::DatabaseConnection,
+    provider: &dyn UsageProvider,
+    publishers: &Publishers,
+    config: &ConfigModel,
+) -> anyhow::Result<(bool, Notification)> {
+    #[cfg(feature = "metrics")]
+    crate::metrics::METRICS.queries_attempted.inc();
+    let result = query_and_record(db, provider, publishers, config).await;
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_query(provider, &result);
+    result
+}
+
+async fn query_and_record(
+    db: &sea_orm::DatabaseConnection,
+    provider: &dyn UsageProvider,
+    publishers: &Publishers,
+    config: &ConfigModel,
+) -> anyhow::Result<(bool, Notification)> {
+    let new_data = fetch_data(db, provider, config).await?;
+
+    record_history(db, &config.user, &new_data).await?;
 
     let daily_model = DailyEntity::find_by_id(config.user.as_str())
         .one(db)
         .await?;
     let last_model = LastEntity::find_by_id(config.user.as_str()).one(db).await?;
 
-    let updated_last = handle_data_update(&new_data, &last_model, &daily_model, config, db).await?;
+    let fired = handle_data_update(&new_data, &last_model, &daily_model, config, db).await?;
 
-    let message = build_message(&new_data, last_model, daily_model)?;
+    let message = build_message(&new_data, last_model.clone(), daily_model.clone())?;
+    let event = UsageEvent {
+        data: new_data,
+        last: last_model,
+        daily: daily_model,
+        fired: fired.clone(),
+    };
+    publishers.publish(&config.user, &event).await;
+    let notification = Notification::new(&config.user, message).with_event(event);
 
-    Ok((updated_last, message))
+    Ok((!fired.is_empty(), notification))
 }
 
 fn build_message(
@@ -87,34 +128,112 @@ fn build_message(
     Ok(message)
 }
 
-fn should_update_last(
+/// the result of a threshold in `should_update_last`
+pub struct ThresholdCheck {
+    pub name: &'static str,
+    pub fired: bool,
+    pub detail: String,
+}
+
+/// evaluate each threshold against the last data, the thresholds not set are skipped
+pub fn check_thresholds(
     config: &ConfigModel,
     new_data: &ChinaUnicomData,
-    last_model: &Option<LastModel>,
-) -> bool {
-    if last_model.is_none() {
-        return true;
-    }
-    let last_model = last_model.as_ref().unwrap();
+    last_model: &LastModel,
+) -> Vec<ThresholdCheck> {
+    let mut checks = Vec::new();
     if let Some(timeout) = config.timeout {
-        if new_data.time - last_model.time > TimeDelta::seconds(timeout) {
-            return true;
-        }
+        let elapsed = new_data.time - last_model.time;
+        checks.push(ThresholdCheck {
+            name: "timeout",
+            fired: elapsed > TimeDelta::seconds(timeout),
+            detail: format!(
+                "{} since last notification, timeout {}",
+                HumanDuration(elapsed.num_seconds()),
+                HumanDuration(timeout)
+            ),
+        });
     }
 
     if let Some(free_threshold) = config.free_threshold {
-        if new_data.free_flow_used - last_model.free_flow_used > free_threshold {
-            return true;
-        }
+        let used = new_data.free_flow_used - last_model.free_flow_used;
+        checks.push(ThresholdCheck {
+            name: "free_threshold",
+            fired: used > free_threshold,
+            detail: format!(
+                "{} free flow used since last notification, threshold {}",
+                HumanSize(used),
+                HumanSize(free_threshold)
+            ),
+        });
     }
 
     if let Some(nonfree_threshold) = config.nonfree_threshold {
-        if new_data.non_free_flow_used - last_model.non_free_flow_used > nonfree_threshold {
-            return true;
-        }
+        let used = new_data.non_free_flow_used - last_model.non_free_flow_used;
+        checks.push(ThresholdCheck {
+            name: "nonfree_threshold",
+            fired: used > nonfree_threshold,
+            detail: format!(
+                "{} non-free flow used since last notification, threshold {}",
+                HumanSize(used),
+                HumanSize(nonfree_threshold)
+            ),
+        });
     }
 
-    false
+    checks
+}
+
+/// the names of the fired thresholds, `no_last` if there is no last data,
+/// the last data is updated if any fired
+fn fired_thresholds(
+    config: &ConfigModel,
+    new_data: &ChinaUnicomData,
+    last_model: &Option<LastModel>,
+) -> Vec<&'static str> {
+    match last_model {
+        Some(last_model) => check_thresholds(config, new_data, last_model)
+            .into_iter()
+            .filter(|check| check.fired)
+            .map(|check| check.name)
+            .collect(),
+        None => vec!["no_last"],
+    }
+}
+
+/// query the data and show which thresholds would fire, nothing is recorded or updated
+pub async fn query_dry_run(
+    db: &sea_orm::DatabaseConnection,
+    provider: &dyn UsageProvider,
+    config: &ConfigModel,
+) -> anyhow::Result<String> {
+    let new_data = fetch_data(db, provider, config).await?;
+    let last_model = LastEntity::find_by_id(config.user.as_str()).one(db).await?;
+
+    let mut message = format!("{}:\n", new_data.package_name);
+    match &last_model {
+        Some(last_model) => {
+            let checks = check_thresholds(config, &new_data, last_model);
+            if checks.is_empty() {
+                message += "No threshold is set.\n";
+            }
+            for check in &checks {
+                message += &format!(
+                    "[{}] {}: {}\n",
+                    if check.fired { "fire" } else { "skip" },
+                    check.name,
+                    check.detail
+                );
+            }
+            if checks.iter().any(|check| check.fired) {
+                message += "A notification would be sent now.";
+            } else {
+                message += "No notification would be sent now.";
+            }
+        }
+        None => message += "There is no last data, a notification would be sent now.",
+    }
+    Ok(message)
 }
 
 async fn handle_data_update(
@@ -123,7 +242,7 @@ async fn handle_data_update(
     daily_model: &Option<DailyModel>,
     config: &ConfigModel,
     db: &sea_orm::DatabaseConnection,
-) -> anyhow::Result<bool> {
+) -> anyhow::Result<Vec<&'static str>> {
     // handle daily data update
     // when the new_data time not equal to the daily data time or the daily data is not exist
     if daily_model.is_none()
@@ -164,9 +283,9 @@ async fn handle_data_update(
     }
 
     // the judge of update last data is complex, so we need to extract it to a function
-    let should_update_today = should_update_last(config, new_data, &last_model);
+    let fired = fired_thresholds(config, new_data, last_model);
 
-    if should_update_today {
+    if !fired.is_empty() {
         if last_model.is_some() {
             LastEntity::delete_by_id(config.user.as_str())
                 .exec(db)
@@ -179,47 +298,127 @@ async fn handle_data_update(
         LastEntity::insert(new_last_active).exec(db).await?;
         tracing::info!("Insert new last data for user: {}", config.user);
     }
-    Ok(should_update_today)
+    Ok(fired)
+}
+
+/// check the credentials of a new config by a test query, refresh the cookie by `online` if expired,
+/// return the config with the refreshed credentials and the queried data,
+/// or an explanation of which credential is wrong
+pub async fn verify_credentials(
+    provider: &dyn UsageProvider,
+    mut config: ConfigModel,
+) -> Result<(ConfigModel, ChinaUnicomData), String> {
+    let error = match provider.fetch_usage(&config.cookie).await {
+        Ok(data) => return Ok((config, data)),
+        Err(e) => e,
+    };
+    if provider.classify_error(&error) != ErrorKind::AuthExpired {
+        return Err(format!(
+            "The test query failed, please check your Cookie: {:?}",
+            error
+        ));
+    }
+
+    // the cookie is expired, try to refresh it with AppId and TokenOnline
+    let resp = provider
+        .refresh_credentials(&config.token_online, &config.app_id)
+        .await
+        .map_err(|e| {
+            format!(
+                "Your Cookie is expired and refreshing it failed, please check your AppId and TokenOnline: {:?}",
+                e
+            )
+        })?;
+    config.token_online = resp.token_online;
+    config.cookie = resp.cookie;
+    match provider.fetch_usage(&config.cookie).await {
+        Ok(data) => Ok((config, data)),
+        Err(e) => Err(format!(
+            "The Cookie refreshed with your AppId and TokenOnline still failed to query: {:?}",
+            e
+        )),
+    }
 }
 
 async fn handle_auth_update(
     config: &ConfigModel,
+    provider: &dyn UsageProvider,
     db: &sea_orm::DatabaseConnection,
 ) -> Result<ConfigModel> {
-    let resp = online(&config.token_online, &config.app_id).await?;
+    let resp = provider
+        .refresh_credentials(&config.token_online, &config.app_id)
+        .await?;
     let mut config_active: ConfigActiveModel = config.clone().into();
-    config_active.token_online = Set(resp.online_token);
+    config_active.token_online = Set(resp.token_online);
     config_active.cookie = Set(resp.cookie);
     let new_config = config_active.update(db).await?;
     Ok(new_config)
 }
 
-pub async fn create_china_unicom_task<DB: Into<sea_orm::DatabaseConnection>>(
-    db: DB,
+/// whether the notifications of the user are snoozed now,
+/// read from the database as the snooze can be changed while the task is running
+async fn is_snoozed(db: &sea_orm::DatabaseConnection, user: &str) -> Result<bool> {
+    Ok(ConfigEntity::find_by_id(user)
+        .one(db)
+        .await?
+        .is_some_and(|config| config.active_snooze().is_some()))
+}
+
+/// what the tasks share, besides the user
+#[derive(Clone)]
+pub struct TaskContext {
+    pub db: sea_orm::DatabaseConnection,
+    pub provider: SharedProvider,
+    pub notifiers: Notifiers,
+    pub publishers: Publishers,
+    pub failures: FailureTracker,
+}
+
+pub async fn create_china_unicom_task(
+    context: TaskContext,
     user: String,
 ) -> anyhow::Result<JoinHandle<()>> {
-    let db = db.into();
+    let TaskContext {
+        db,
+        provider,
+        notifiers,
+        publishers,
+        failures,
+    } = context;
 
     let config = ConfigEntity::find_by_id(&user)
         .one(&db)
         .await?
         .ok_or(anyhow::anyhow!("User {} not found in config", user))?;
 
-    let (shoudl_send, message) = query_once(&db, &config).await?;
+    let (shoudl_send, notification) =
+        match query_once(&db, provider.as_ref(), &publishers, &config).await {
+            Ok(result) => {
+                failures.success(&user);
+                result
+            }
+            Err(e) => {
+                failures.record(&user, &e.to_string());
+                return Err(e);
+            }
+        };
 
-    if shoudl_send {
-        send_message(&user, &config.bot, message).await?;
+    if shoudl_send && !is_snoozed(&db, &user).await? {
+        notifiers.dispatch(&db, &notification).await?;
     }
 
     let handle = tokio::spawn(async move {
         let mut retry = 3;
-        let interval = std::time::Duration::from_secs(config.interval as u64);
+        let interval = std::time::Duration::from_secs(config.interval.max(MIN_INTERVAL) as u64);
         while retry > 0 {
             sleep(interval).await;
-            match query_once(&db, &config).await {
-                Ok((should_send, message)) => {
-                    if should_send {
-                        match send_message(&user, &config.bot, message).await {
+            match query_once(&db, provider.as_ref(), &publishers, &config).await {
+                Ok((should_send, notification)) => {
+                    failures.success(&user);
+                    if should_send && is_snoozed(&db, &user).await.unwrap_or(false) {
+                        tracing::info!("Notification snoozed for user: {}", user);
+                    } else if should_send {
+                        match notifiers.dispatch(&db, &notification).await {
                             Ok(_) => retry = 3,
                             Err(e) => {
                                 tracing::error!(
@@ -237,6 +436,7 @@ pub async fn create_china_unicom_task<DB: Into<sea_orm::DatabaseConnection>>(
                         retry,
                         e
                     );
+                    failures.record(&user, &e.to_string());
                     retry -= 1;
                 }
             }
@@ -244,3 +444,210 @@ pub async fn create_china_unicom_task<DB: Into<sea_orm::DatabaseConnection>>(
     });
     Ok(handle)
 }
+
+#[cfg(test)]
+mod test {
+    use china_unicom_rs::data::ChinaUnicomData;
+    use chrono::{Local, TimeDelta};
+    use std::sync::atomic::Ordering;
+
+    use sea_orm::{EntityTrait, IntoActiveModel, Set};
+
+    use super::{check_thresholds, create_china_unicom_task, query_once, verify_credentials};
+    use crate::{
+        model::{
+            target::{build_target_active, TargetKind},
+            ConfigEntity, ConfigModel, DailyEntity, HistoryEntity, LastEntity, LastModel,
+            TargetEntity,
+        },
+        testing::{at, fake_context, insert_config, usage, ScriptedProvider},
+    };
+
+    #[test]
+    fn thresholds() {
+        let config = ConfigModel::default();
+        let last = LastModel::default();
+        let mut data = ChinaUnicomData {
+            time: last.time + TimeDelta::minutes(10),
+            non_free_flow_used: last.non_free_flow_used + 0.1,
+            ..Default::default()
+        };
+        let fired: Vec<_> = check_thresholds(&config, &data, &last)
+            .into_iter()
+            .filter(|check| check.fired)
+            .map(|check| check.name)
+            .collect();
+        assert_eq!(fired, vec!["nonfree_threshold"]);
+
+        data.non_free_flow_used = last.non_free_flow_used;
+        assert!(check_thresholds(&config, &data, &last)
+            .iter()
+            .all(|check| !check.fired));
+    }
+
+    #[tokio::test]
+    async fn register_verify() {
+        let provider = ScriptedProvider::default();
+        let config = ConfigModel {
+            cookie: "cookie".to_string(),
+            ..Default::default()
+        };
+
+        // an expired cookie is refreshed
+        provider
+            .push_auth_expired()
+            .push_usage(usage(at(1, 8, 0), 1.0));
+        let (config, data) = verify_credentials(&provider, config).await.unwrap();
+        assert_eq!(config.cookie, "refreshed_cookie_1");
+        assert_eq!(config.token_online, "refreshed_token_1");
+        assert_eq!(data.package_name, "test package");
+
+        provider.push_error("invalid cookie");
+        let error = verify_credentials(&provider, config.clone())
+            .await
+            .unwrap_err();
+        assert!(error.contains("check your Cookie"));
+
+        *provider.refresh_error.lock().unwrap() = Some("invalid token".to_string());
+        provider.push_auth_expired();
+        let error = verify_credentials(&provider, config).await.unwrap_err();
+        assert!(error.contains("check your AppId and TokenOnline"));
+    }
+
+    #[tokio::test]
+    async fn query_and_daily_rollover() {
+        let (context, provider, _sink) = fake_context().await;
+        let config = insert_config(&context.db, "test_1").await;
+        provider
+            .push_usage(usage(at(1, 8, 0), 1.0))
+            .push_usage(usage(at(1, 8, 5), 1.01))
+            .push_usage(usage(at(2, 8, 0), 1.1));
+
+        // no last data yet
+        let (should_send, _) =
+            query_once(&context.db, provider.as_ref(), &context.publishers, &config)
+                .await
+                .unwrap();
+        assert!(should_send);
+
+        // below the thresholds
+        let (should_send, _) =
+            query_once(&context.db, provider.as_ref(), &context.publishers, &config)
+                .await
+                .unwrap();
+        assert!(!should_send);
+
+        // a new day, the daily data is replaced by the last data
+        let (should_send, _) =
+            query_once(&context.db, provider.as_ref(), &context.publishers, &config)
+                .await
+                .unwrap();
+        assert!(should_send);
+        let daily = DailyEntity::find_by_id("test_1")
+            .one(&context.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(daily.time, at(1, 8, 0));
+        let last = LastEntity::find_by_id("test_1")
+            .one(&context.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.time, at(2, 8, 0));
+        assert_eq!(
+            HistoryEntity::find().all(&context.db).await.unwrap().len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn auth_refresh() {
+        let (context, provider, _sink) = fake_context().await;
+        let config = insert_config(&context.db, "test_1").await;
+        provider
+            .push_auth_expired()
+            .push_usage(usage(at(1, 8, 0), 1.0));
+
+        query_once(&context.db, provider.as_ref(), &context.publishers, &config)
+            .await
+            .unwrap();
+        let config = ConfigEntity::find_by_id("test_1")
+            .one(&context.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.cookie, "refreshed_cookie_1");
+        assert_eq!(config.token_online, "refreshed_token_1");
+        assert_eq!(
+            *provider.cookies.lock().unwrap(),
+            vec!["cookie", "refreshed_cookie_1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn task_notification() {
+        let (context, provider, sink) = fake_context().await;
+        let config = insert_config(&context.db, "test_1").await;
+        TargetEntity::insert(build_target_active(
+            "test_1".to_string(),
+            TargetKind::Group,
+            "test_group".to_string(),
+            "test_bot".to_string(),
+        ))
+        .exec(&context.db)
+        .await
+        .unwrap();
+
+        // the first query of a task always notifies, the groups are notified too
+        provider.push_usage(usage(at(1, 8, 0), 1.0));
+        create_china_unicom_task(context.clone(), "test_1".to_string())
+            .await
+            .unwrap()
+            .abort();
+        let messages = sink.take();
+        let targets: Vec<&str> = messages.iter().map(|(target, _)| target.as_str()).collect();
+        assert_eq!(targets, vec!["private:test_1", "group:test_group"]);
+        assert!(messages[1].1.starts_with("test_1:\n"));
+
+        // a failed target is tracked, the others are still delivered
+        sink.fail_groups.store(true, Ordering::SeqCst);
+        provider.push_usage(usage(at(1, 8, 30), 2.0));
+        create_china_unicom_task(context.clone(), "test_1".to_string())
+            .await
+            .unwrap()
+            .abort();
+        assert_eq!(sink.take().len(), 1);
+        let group = TargetEntity::find_by_id(2)
+            .one(&context.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(group.failures, 1);
+        assert!(group.last_error.is_some());
+        sink.fail_groups.store(false, Ordering::SeqCst);
+
+        // over the threshold but snoozed
+        let mut config_active = config.into_active_model();
+        config_active.snooze_until = Set(Some(Local::now() + TimeDelta::hours(1)));
+        ConfigEntity::update(config_active)
+            .exec(&context.db)
+            .await
+            .unwrap();
+        provider.push_usage(usage(at(1, 9, 0), 3.0));
+        create_china_unicom_task(context.clone(), "test_1".to_string())
+            .await
+            .unwrap()
+            .abort();
+        assert!(sink.take().is_empty());
+
+        // a failed query is tracked
+        provider.push_error("network error");
+        assert!(
+            create_china_unicom_task(context.clone(), "test_1".to_string())
+                .await
+                .is_err()
+        );
+        assert_eq!(context.failures.get("test_1").unwrap().consecutive, 1);
+    }
+}
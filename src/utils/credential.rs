@@ -0,0 +1,297 @@
+use serde_json::Value;
+
+use crate::model::ConfigModel;
+
+use super::redact::mask_secret;
+
+/// the credentials found in a pasted capture of a request
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Credentials {
+    pub cookie: Option<String>,
+    pub app_id: Option<String>,
+    pub token_online: Option<String>,
+}
+
+impl Credentials {
+    pub fn is_empty(&self) -> bool {
+        self.cookie.is_none() && self.app_id.is_none() && self.token_online.is_none()
+    }
+
+    /// keep the first value found of each credential
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        let slot = match key
+            .trim()
+            .to_ascii_lowercase()
+            .replace(['_', '-'], "")
+            .as_str()
+        {
+            "cookie" => &mut self.cookie,
+            "appid" => &mut self.app_id,
+            "tokenonline" => &mut self.token_online,
+            _ => return,
+        };
+        if slot.is_none() {
+            *slot = Some(if key.eq_ignore_ascii_case("cookie") {
+                normalize_cookie(value)
+            } else {
+                value.to_string()
+            });
+        }
+    }
+
+    /// the found credentials with secrets masked, to confirm with the user
+    pub fn summary(&self) -> String {
+        let show = |value: &Option<String>| match value {
+            Some(value) => mask_secret(value),
+            None => "not found".to_string(),
+        };
+        format!(
+            "Cookie: {}\nAppId: {}\nTokenOnline: {}",
+            show(&self.cookie),
+            show(&self.app_id),
+            show(&self.token_online)
+        )
+    }
+
+    /// fill the found credentials into the config, return the names of the updated fields
+    pub fn apply(self, config: &mut ConfigModel) -> Vec<&'static str> {
+        let mut updated = Vec::new();
+        if let Some(cookie) = self.cookie {
+            config.cookie = cookie;
+            updated.push("cookie");
+        }
+        if let Some(app_id) = self.app_id {
+            config.app_id = app_id;
+            updated.push("app_id");
+        }
+        if let Some(token_online) = self.token_online {
+            config.token_online = token_online;
+            updated.push("token_online");
+        }
+        updated
+    }
+}
+
+/// strip the `Cookie:` prefix and the whitespace around each pair
+pub fn normalize_cookie(text: &str) -> String {
+    let text = text.trim();
+    let text = match text.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("cookie:") => &text[7..],
+        _ => text,
+    };
+    text.split(';')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// extract the credentials from a pasted curl command, raw http request or har/json fragment,
+/// return `None` if the text is not a capture of a request,
+/// a truncated capture gives the credentials found before the cut
+pub fn extract_credentials(text: &str) -> Option<Credentials> {
+    let text = text.trim();
+    let mut credentials = Credentials::default();
+    if text.starts_with('{') || text.starts_with('[') {
+        if let Ok(value) = serde_json::from_str::<Value>(text) {
+            collect_json(&value, &mut credentials);
+        }
+    } else if text.starts_with("curl ") {
+        // a truncated command is still a capture, not a cookie
+        let _ = collect_curl(text, &mut credentials);
+    } else if is_raw_http(text) {
+        collect_raw_http(text, &mut credentials);
+    } else {
+        return None;
+    }
+    Some(credentials)
+}
+
+/// a request line, or several lines with a cookie header
+fn is_raw_http(text: &str) -> bool {
+    let is_cookie_header = |line: &str| {
+        line.split_once(':')
+            .is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case("cookie"))
+    };
+    text.lines()
+        .next()
+        .is_some_and(|line| line.contains(" HTTP/"))
+        || text.lines().count() > 1 && text.lines().any(is_cookie_header)
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// collect `key=value` pairs of a form body or a url query
+fn collect_form(text: &str, credentials: &mut Credentials) {
+    let query = text.split_once('?').map_or(text, |(_, query)| query);
+    for pair in query.split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            credentials.set(&percent_decode(key), &percent_decode(value));
+        }
+    }
+}
+
+fn collect_header(header: &str, credentials: &mut Credentials) {
+    if let Some((name, value)) = header.split_once(':') {
+        credentials.set(name, value);
+    }
+}
+
+fn collect_json(value: &Value, credentials: &mut Credentials) {
+    match value {
+        Value::Object(map) => {
+            // headers and params of har are in the form of {"name": .., "value": ..}
+            if let (Some(Value::String(name)), Some(Value::String(value))) =
+                (map.get("name"), map.get("value"))
+            {
+                credentials.set(name, value);
+            }
+            for (key, value) in map {
+                match value {
+                    Value::String(text) => {
+                        credentials.set(key, text);
+                        // the post body and the url of har
+                        if key == "text" || key == "url" {
+                            collect_form(text, credentials);
+                        }
+                    }
+                    other => collect_json(other, credentials),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_json(item, credentials);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// return `None` if the command is truncated, the credentials before the cut are kept
+fn collect_curl(text: &str, credentials: &mut Credentials) -> Option<()> {
+    let text = text.replace("\\\r\n", " ").replace("\\\n", " ");
+    let args = shlex::split(&text)?;
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-H" | "--header" => collect_header(args.next()?, credentials),
+            "-b" | "--cookie" => credentials.set("cookie", args.next()?),
+            "-d" | "--data" | "--data-raw" | "--data-binary" | "--data-ascii"
+            | "--data-urlencode" => collect_form(args.next()?, credentials),
+            arg if arg.starts_with("-H") => collect_header(&arg[2..], credentials),
+            arg if arg.starts_with("http://") || arg.starts_with("https://") => {
+                collect_form(arg, credentials)
+            }
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+fn collect_raw_http(text: &str, credentials: &mut Credentials) {
+    let mut lines = text.lines();
+    let mut in_body = false;
+    let mut body = String::new();
+    if let Some(first) = lines.next() {
+        match first.split_whitespace().nth(1) {
+            Some(path) if first.contains(" HTTP/") => collect_form(path, credentials),
+            _ => collect_header(first, credentials),
+        }
+    }
+    for line in lines {
+        if in_body {
+            body += line;
+        } else if line.trim().is_empty() {
+            in_body = true;
+        } else {
+            collect_header(line, credentials);
+        }
+    }
+    let body = body.trim();
+    if body.starts_with('{') {
+        if let Ok(value) = serde_json::from_str::<Value>(body) {
+            collect_json(&value, credentials);
+        }
+    } else if !body.is_empty() {
+        collect_form(body, credentials);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{extract_credentials, normalize_cookie};
+
+    #[test]
+    fn cookie() {
+        assert_eq!(normalize_cookie(" Cookie: a=1;\n b=2; "), "a=1; b=2");
+        assert_eq!(normalize_cookie("a=1"), "a=1");
+        assert!(extract_credentials("Cookie: a=1; b=2").is_none());
+    }
+
+    #[test]
+    fn curl() {
+        let text = "curl -X POST 'https://m.client.10010.com/mobileService/onLine.htm' \\\n  -H 'Cookie: a=1; b=2' \\\n  --data-raw 'token_online=abc%2B1&appId=app'";
+        let credentials = extract_credentials(text).unwrap();
+        assert_eq!(credentials.cookie.as_deref(), Some("a=1; b=2"));
+        assert_eq!(credentials.app_id.as_deref(), Some("app"));
+        assert_eq!(credentials.token_online.as_deref(), Some("abc+1"));
+
+        // truncated pastes are still captures, never taken as a cookie
+        let credentials =
+            extract_credentials("curl 'https://a.com' -H 'Cookie: a=1' --data-raw").unwrap();
+        assert_eq!(credentials.cookie.as_deref(), Some("a=1"));
+        assert!(extract_credentials("curl 'https://a.com' -H 'Cookie: a=1")
+            .unwrap()
+            .is_empty());
+        assert!(extract_credentials("{\"log\": {\"entries\": [")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn raw_http() {
+        let text = "POST /mobileService/onLine.htm?appId=app HTTP/1.1\nHost: m.client.10010.com\ncookie: a=1\n\ntoken_online=abc";
+        let credentials = extract_credentials(text).unwrap();
+        assert_eq!(credentials.cookie.as_deref(), Some("a=1"));
+        assert_eq!(credentials.app_id.as_deref(), Some("app"));
+        assert_eq!(credentials.token_online.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn har() {
+        let text = r#"{"request": {"url": "https://m.client.10010.com/x?appId=app",
+            "headers": [{"name": "Cookie", "value": "a=1"}],
+            "postData": {"params": [{"name": "token_online", "value": "abc"}]}}}"#;
+        let credentials = extract_credentials(text).unwrap();
+        assert_eq!(credentials.cookie.as_deref(), Some("a=1"));
+        assert_eq!(credentials.app_id.as_deref(), Some("app"));
+        assert_eq!(credentials.token_online.as_deref(), Some("abc"));
+    }
+}
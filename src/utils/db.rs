@@ -1,6 +1,13 @@
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use sea_orm_migration::MigratorTrait as _;
 
-use crate::migration::Migrator;
+use crate::{
+    migration::Migrator,
+    model::{
+        api_token, history, target, ApiTokenEntity, ConfigEntity, DailyEntity, HistoryEntity,
+        LastEntity, TargetEntity,
+    },
+};
 
 pub async fn init_db() -> anyhow::Result<sea_orm::DatabaseConnection> {
     let path = std::path::Path::new("./china_unicom/data.db");
@@ -10,8 +17,107 @@ pub async fn init_db() -> anyhow::Result<sea_orm::DatabaseConnection> {
         std::fs::create_dir_all(parent)?;
         std::fs::File::create(path)?;
     }
-    let connect_options = sea_orm::ConnectOptions::new("sqlite://./china_unicom/data.db");
+    connect_db("sqlite://./china_unicom/data.db").await
+}
+
+/// connect to the database and run the migrations, `sqlite::memory:` gives an empty database
+pub async fn connect_db(url: &str) -> anyhow::Result<sea_orm::DatabaseConnection> {
+    let mut connect_options = sea_orm::ConnectOptions::new(url);
+    if url.contains(":memory:") {
+        // an in-memory database is dropped with its last connection, so keep exactly one
+        connect_options.max_connections(1).min_connections(1);
+    }
     let db = sea_orm::Database::connect(connect_options).await?;
     Migrator::up(&db, None).await?;
     Ok(db)
 }
+
+/// delete all the data of the user, the config is deleted last
+pub async fn delete_user(
+    db: &sea_orm::DatabaseConnection,
+    user: &str,
+) -> Result<(), sea_orm::DbErr> {
+    let _ = LastEntity::delete_by_id(user).exec(db).await;
+    let _ = DailyEntity::delete_by_id(user).exec(db).await;
+    let _ = HistoryEntity::delete_many()
+        .filter(history::Column::User.eq(user))
+        .exec(db)
+        .await;
+    let _ = TargetEntity::delete_many()
+        .filter(target::Column::User.eq(user))
+        .exec(db)
+        .await;
+    let _ = ApiTokenEntity::delete_many()
+        .filter(api_token::Column::User.eq(user))
+        .exec(db)
+        .await;
+    ConfigEntity::delete_by_id(user).exec(db).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sea_orm::EntityTrait;
+
+    use super::delete_user;
+    use crate::{
+        model::{
+            api_token::build_api_token_active,
+            target::{build_target_active, TargetKind},
+            ApiTokenEntity, ConfigEntity, DailyEntity, HistoryEntity, LastEntity, TargetEntity,
+        },
+        testing::{at, fake_context, insert_config, usage},
+        utils::china_unicom::query_once,
+    };
+
+    #[tokio::test]
+    async fn deregister() {
+        let (context, provider, _sink) = fake_context().await;
+        for user in ["test_1", "test_2"] {
+            let config = insert_config(&context.db, user).await;
+            provider.push_usage(usage(at(1, 8, 0), 1.0));
+            query_once(&context.db, provider.as_ref(), &context.publishers, &config)
+                .await
+                .unwrap();
+            TargetEntity::insert(build_target_active(
+                user.to_string(),
+                TargetKind::Group,
+                "test_group".to_string(),
+                "test_bot".to_string(),
+            ))
+            .exec(&context.db)
+            .await
+            .unwrap();
+            ApiTokenEntity::insert(build_api_token_active(
+                user.to_string(),
+                "dashboard".to_string(),
+                format!("hash_{}", user),
+            ))
+            .exec(&context.db)
+            .await
+            .unwrap();
+        }
+
+        delete_user(&context.db, "test_1").await.unwrap();
+        let users: Vec<String> = ConfigEntity::find()
+            .all(&context.db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|config| config.user)
+            .collect();
+        assert_eq!(users, vec!["test_2"]);
+        assert_eq!(LastEntity::find().all(&context.db).await.unwrap().len(), 1);
+        assert_eq!(DailyEntity::find().all(&context.db).await.unwrap().len(), 1);
+        assert_eq!(
+            HistoryEntity::find().all(&context.db).await.unwrap().len(),
+            1
+        );
+        let targets = TargetEntity::find().all(&context.db).await.unwrap();
+        assert_eq!(targets.len(), 2);
+        assert!(targets.iter().all(|target| target.user == "test_2"));
+        let tokens = ApiTokenEntity::find().all(&context.db).await.unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].user, "test_2");
+    }
+}
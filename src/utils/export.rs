@@ -0,0 +1,270 @@
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{
+    history, ConfigModel, DailyEntity, DailyModel, HistoryEntity, HistoryModel, LastEntity,
+    LastModel,
+};
+
+/// the version of the export format
+pub const EXPORT_VERSION: u32 = 1;
+
+/// max chars of a text message when the file can not be sent
+pub const TEXT_CHUNK_SIZE: usize = 3000;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/// all the data of a user
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportData {
+    pub version: u32,
+    pub exported_at: DateTime<Local>,
+    pub config: serde_json::Value,
+    pub last: Option<LastModel>,
+    pub daily: Option<DailyModel>,
+    pub history: Vec<HistoryModel>,
+}
+
+/// build the export of the user, secrets of the config are masked unless `with_secrets`
+pub async fn build_export(
+    db: &sea_orm::DatabaseConnection,
+    config: &ConfigModel,
+    with_secrets: bool,
+) -> anyhow::Result<ExportData> {
+    let last = LastEntity::find_by_id(config.user.as_str()).one(db).await?;
+    let daily = DailyEntity::find_by_id(config.user.as_str())
+        .one(db)
+        .await?;
+    let history = HistoryEntity::find()
+        .filter(history::Column::User.eq(config.user.as_str()))
+        .order_by_asc(history::Column::Time)
+        .all(db)
+        .await?;
+    Ok(ExportData {
+        version: EXPORT_VERSION,
+        exported_at: Local::now(),
+        config: config.to_json(!with_secrets),
+        last,
+        daily,
+        history,
+    })
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+const CSV_HEADER: &str = "kind,time,package_name,sum_flow_used,limit_flow_used,non_limit_flow_used,free_flow_used,non_free_flow_used,sum_voice_used";
+
+impl ExportData {
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// the config as `#` comment lines, followed by one row per snapshot and history reading
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        if let Some(config) = self.config.as_object() {
+            for (key, value) in config {
+                csv += &format!("# {}: {}\n", key, value);
+            }
+        }
+        csv += CSV_HEADER;
+        csv += "\n";
+        let mut row = |kind: &str,
+                       time: &DateTime<Local>,
+                       package_name: &str,
+                       flows: [f64; 5],
+                       sum_voice_used: i64| {
+            csv += &format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                kind,
+                time.to_rfc3339(),
+                csv_field(package_name),
+                flows[0],
+                flows[1],
+                flows[2],
+                flows[3],
+                flows[4],
+                sum_voice_used
+            );
+        };
+        if let Some(last) = &self.last {
+            row(
+                "last",
+                &last.time,
+                &last.package_name,
+                [
+                    last.sum_flow_used,
+                    last.limit_flow_used,
+                    last.non_limit_flow_used,
+                    last.free_flow_used,
+                    last.non_free_flow_used,
+                ],
+                last.sum_voice_used,
+            );
+        }
+        if let Some(daily) = &self.daily {
+            row(
+                "daily",
+                &daily.time,
+                &daily.package_name,
+                [
+                    daily.sum_flow_used,
+                    daily.limit_flow_used,
+                    daily.non_limit_flow_used,
+                    daily.free_flow_used,
+                    daily.non_free_flow_used,
+                ],
+                daily.sum_voice_used,
+            );
+        }
+        for reading in &self.history {
+            row(
+                "history",
+                &reading.time,
+                "",
+                [
+                    reading.sum_flow_used,
+                    reading.limit_flow_used,
+                    reading.non_limit_flow_used,
+                    reading.free_flow_used,
+                    reading.non_free_flow_used,
+                ],
+                reading.sum_voice_used,
+            );
+        }
+        csv
+    }
+}
+
+/// split the text into chunks of at most `max_chars` chars, at line boundaries when possible
+pub fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    let mut chunk_chars = 0;
+    for line in text.split_inclusive('\n') {
+        let line_chars = line.chars().count();
+        if chunk_chars + line_chars > max_chars && !chunk.is_empty() {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_chars = 0;
+        }
+        if line_chars > max_chars {
+            // a single line longer than the limit has to be split
+            let chars: Vec<char> = line.chars().collect();
+            for part in chars.chunks(max_chars) {
+                chunks.push(part.iter().collect());
+            }
+            continue;
+        }
+        chunk += line;
+        chunk_chars += line_chars;
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use sea_orm::EntityTrait;
+
+    use super::{build_export, chunk_text, csv_field};
+    use crate::{
+        model::{ConfigEntity, HistoryEntity, LastEntity},
+        testing::{at, fake_context, insert_config, memory_db, usage},
+        utils::{
+            china_unicom::query_once,
+            import::{import_user, parse_import, ImportMode},
+        },
+    };
+
+    #[test]
+    fn chunk() {
+        assert_eq!(chunk_text("a\nb\nc\n", 4), vec!["a\nb\n", "c\n"]);
+        assert_eq!(chunk_text("abcdef", 4), vec!["abcd", "ef"]);
+        assert!(chunk_text("", 4).is_empty());
+    }
+
+    #[test]
+    fn csv_escape() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let (context, provider, _sink) = fake_context().await;
+        let config = insert_config(&context.db, "test_1").await;
+        provider
+            .push_usage(usage(at(1, 8, 0), 1.0))
+            .push_usage(usage(at(1, 9, 0), 1.5));
+        for _ in 0..2 {
+            query_once(&context.db, provider.as_ref(), &context.publishers, &config)
+                .await
+                .unwrap();
+        }
+
+        let export = build_export(&context.db, &config, false).await.unwrap();
+        assert_eq!(export.config["settings"]["cookie"], "****");
+        assert_eq!(export.config["app_id"], "****");
+        assert_eq!(export.config["token_online"], "****");
+        assert_eq!(export.history.len(), 2);
+        assert!(export.last.is_some() && export.daily.is_some());
+
+        let export = build_export(&context.db, &config, true).await.unwrap();
+        let exports = parse_import(&export.to_json().unwrap()).unwrap();
+        assert_eq!(exports.len(), 1);
+        let db = memory_db().await;
+        let report = import_user(
+            &db,
+            exports.into_iter().next().unwrap(),
+            ImportMode::Replace,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.user, "test_1");
+        assert!(report.last && report.daily);
+        assert_eq!(report.history, 2);
+
+        let restored = ConfigEntity::find_by_id("test_1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored.cookie, config.cookie);
+        assert_eq!(restored.app_id, config.app_id);
+        assert_eq!(restored.token_online, config.token_online);
+        assert_eq!(restored.nonfree_threshold, config.nonfree_threshold);
+        let last = LastEntity::find_by_id("test_1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            last.non_free_flow_used,
+            export.last.unwrap().non_free_flow_used
+        );
+        assert_eq!(HistoryEntity::find().all(&db).await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+use dashmap::DashMap;
+
+/// the query failures of a user's task
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub total: u32,
+    pub consecutive: u32,
+    pub last_error: String,
+    pub last_time: DateTime<Local>,
+}
+
+/// the query failures of all tasks, shared between the handler and the tasks
+#[derive(Clone, Default)]
+pub struct FailureTracker(Arc<DashMap<String, Failure>>);
+
+impl FailureTracker {
+    pub fn record(&self, user: &str, error: &str) {
+        let mut failure = self.0.entry(user.to_string()).or_insert_with(|| Failure {
+            total: 0,
+            consecutive: 0,
+            last_error: String::new(),
+            last_time: Local::now(),
+        });
+        failure.total += 1;
+        failure.consecutive += 1;
+        failure.last_error = error.to_string();
+        failure.last_time = Local::now();
+    }
+
+    /// a successful query resets the consecutive count
+    pub fn success(&self, user: &str) {
+        if let Some(mut failure) = self.0.get_mut(user) {
+            failure.consecutive = 0;
+        }
+    }
+
+    pub fn get(&self, user: &str) -> Option<Failure> {
+        self.0.get(user).map(|failure| failure.clone())
+    }
+
+    /// all failures, the latest first
+    pub fn all(&self) -> Vec<(String, Failure)> {
+        let mut failures: Vec<(String, Failure)> = self
+            .0
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        failures.sort_by_key(|(_, failure)| std::cmp::Reverse(failure.last_time));
+        failures
+    }
+
+    pub fn remove(&self, user: &str) {
+        self.0.remove(user);
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    collections::BTreeMap,
+    ops::{AddAssign, Sub},
+};
+
+use china_unicom_rs::data::ChinaUnicomData;
+use chrono::{DateTime, Local, NaiveDate, TimeDelta};
+use clap::ValueEnum;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::Serialize;
+
+use crate::model::{
+    history::{build_history_active, Column},
+    HistoryEntity, HistoryModel,
+};
+
+use super::units::HumanSize;
+
+/// lines per page of the history output
+pub const HISTORY_PAGE_SIZE: usize = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Granularity {
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    fn label(&self, time: &DateTime<Local>) -> String {
+        match self {
+            Granularity::Hour => time.format("%Y-%m-%d %H:00").to_string(),
+            Granularity::Day => time.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// the used data delta in a bucket, the usage of a single reading is the delta since the
+/// monthly reset
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct UsageDelta {
+    pub sum_flow_used: f64,
+    pub limit_flow_used: f64,
+    pub non_limit_flow_used: f64,
+    pub free_flow_used: f64,
+    pub non_free_flow_used: f64,
+    pub sum_voice_used: i64,
+}
+
+impl UsageDelta {
+    /// the usage from `old` to this one, a decrease is taken as the monthly reset
+    pub fn since(&self, old: &UsageDelta) -> UsageDelta {
+        UsageDelta {
+            sum_flow_used: delta(self.sum_flow_used, old.sum_flow_used),
+            limit_flow_used: delta(self.limit_flow_used, old.limit_flow_used),
+            non_limit_flow_used: delta(self.non_limit_flow_used, old.non_limit_flow_used),
+            free_flow_used: delta(self.free_flow_used, old.free_flow_used),
+            non_free_flow_used: delta(self.non_free_flow_used, old.non_free_flow_used),
+            sum_voice_used: delta(self.sum_voice_used, old.sum_voice_used),
+        }
+    }
+}
+
+impl From<&ChinaUnicomData> for UsageDelta {
+    fn from(data: &ChinaUnicomData) -> Self {
+        Self {
+            sum_flow_used: data.sum_flow_used,
+            limit_flow_used: data.limit_flow_used,
+            non_limit_flow_used: data.non_limit_flow_used,
+            free_flow_used: data.free_flow_used,
+            non_free_flow_used: data.non_free_flow_used,
+            sum_voice_used: data.sum_voice_used,
+        }
+    }
+}
+
+impl From<&HistoryModel> for UsageDelta {
+    fn from(reading: &HistoryModel) -> Self {
+        Self {
+            sum_flow_used: reading.sum_flow_used,
+            limit_flow_used: reading.limit_flow_used,
+            non_limit_flow_used: reading.non_limit_flow_used,
+            free_flow_used: reading.free_flow_used,
+            non_free_flow_used: reading.non_free_flow_used,
+            sum_voice_used: reading.sum_voice_used,
+        }
+    }
+}
+
+impl AddAssign for UsageDelta {
+    fn add_assign(&mut self, other: UsageDelta) {
+        self.sum_flow_used += other.sum_flow_used;
+        self.limit_flow_used += other.limit_flow_used;
+        self.non_limit_flow_used += other.non_limit_flow_used;
+        self.free_flow_used += other.free_flow_used;
+        self.non_free_flow_used += other.non_free_flow_used;
+        self.sum_voice_used += other.sum_voice_used;
+    }
+}
+
+/// record a reading, skipped when the usage is the same as the latest one
+pub async fn record_history(
+    db: &sea_orm::DatabaseConnection,
+    user: &str,
+    data: &ChinaUnicomData,
+) -> anyhow::Result<()> {
+    let latest = HistoryEntity::find()
+        .filter(Column::User.eq(user))
+        .order_by_desc(Column::Time)
+        .one(db)
+        .await?;
+    if let Some(latest) = latest {
+        if latest.same_usage(data) {
+            return Ok(());
+        }
+    }
+    HistoryEntity::insert(build_history_active(data, user.to_string()))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+fn start_of(date: NaiveDate) -> anyhow::Result<DateTime<Local>> {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|time| time.and_local_timezone(Local).earliest())
+        .ok_or(anyhow::anyhow!("Invalid date: {}", date))
+}
+
+/// get the readings in `[from, to]` (both dates included),
+/// and the latest reading before `from` as the base of the first delta
+pub async fn query_history(
+    db: &sea_orm::DatabaseConnection,
+    user: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> anyhow::Result<(Option<HistoryModel>, Vec<HistoryModel>)> {
+    let start = start_of(from)?;
+    let end = start_of(to)? + TimeDelta::days(1);
+
+    let base = HistoryEntity::find()
+        .filter(Column::User.eq(user))
+        .filter(Column::Time.lt(start))
+        .order_by_desc(Column::Time)
+        .one(db)
+        .await?;
+    let readings = HistoryEntity::find()
+        .filter(Column::User.eq(user))
+        .filter(Column::Time.gte(start))
+        .filter(Column::Time.lt(end))
+        .order_by_asc(Column::Time)
+        .all(db)
+        .await?;
+    Ok((base, readings))
+}
+
+/// the used data is reset every month, so a decreasing value means a reset
+fn delta<T: PartialOrd + Sub<Output = T>>(new: T, old: T) -> T {
+    if new >= old {
+        new - old
+    } else {
+        new
+    }
+}
+
+/// sum the deltas of consecutive readings into buckets, keyed by the bucket label
+pub fn bucket_deltas(
+    base: Option<&HistoryModel>,
+    readings: &[HistoryModel],
+    granularity: Granularity,
+) -> BTreeMap<String, UsageDelta> {
+    let mut buckets: BTreeMap<String, UsageDelta> = BTreeMap::new();
+    let mut previous = base;
+    for reading in readings {
+        let bucket = buckets.entry(granularity.label(&reading.time)).or_default();
+        if let Some(previous) = previous {
+            *bucket += UsageDelta::from(reading).since(&UsageDelta::from(previous));
+        }
+        previous = Some(reading);
+    }
+    buckets
+}
+
+/// format one page of the buckets, `page` starts from 1
+pub fn format_history_page(buckets: &BTreeMap<String, UsageDelta>, page: usize) -> String {
+    if buckets.is_empty() {
+        return "No history data in this range.".to_string();
+    }
+    let pages = buckets.len().div_ceil(HISTORY_PAGE_SIZE);
+    let page = page.clamp(1, pages);
+    let mut message = String::new();
+    for (label, delta) in buckets
+        .iter()
+        .skip((page - 1) * HISTORY_PAGE_SIZE)
+        .take(HISTORY_PAGE_SIZE)
+    {
+        message += &format!(
+            "{}: 总: {}, 跳: {}, 免: {}\n",
+            label,
+            HumanSize(delta.sum_flow_used),
+            HumanSize(delta.non_free_flow_used),
+            HumanSize(delta.free_flow_used)
+        );
+    }
+    message += &format!("Page {}/{}", page, pages);
+    if page < pages {
+        message += &format!(", use `--page {}` to see more", page + 1);
+    }
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Local, TimeZone};
+
+    use super::{bucket_deltas, format_history_page, Granularity, UsageDelta, HISTORY_PAGE_SIZE};
+    use crate::model::HistoryModel;
+
+    fn reading(day: u32, hour: u32, non_free_flow_used: f64) -> HistoryModel {
+        HistoryModel {
+            id: 0,
+            user: "1".to_string(),
+            time: Local.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap(),
+            sum_flow_used: non_free_flow_used,
+            limit_flow_used: 0.0,
+            non_limit_flow_used: 0.0,
+            free_flow_used: 0.0,
+            non_free_flow_used,
+            sum_voice_used: 0,
+        }
+    }
+
+    #[test]
+    fn bucket() {
+        let base = reading(1, 0, 1.0);
+        let readings = vec![
+            reading(2, 1, 1.5),
+            reading(2, 2, 2.0),
+            reading(3, 1, 3.0),
+            // reset of a new month
+            reading(4, 1, 0.5),
+        ];
+        let days = bucket_deltas(Some(&base), &readings, Granularity::Day);
+        let deltas: Vec<f64> = days.values().map(|d| d.non_free_flow_used).collect();
+        assert_eq!(deltas, vec![1.0, 1.0, 0.5]);
+
+        let hours = bucket_deltas(None, &readings, Granularity::Hour);
+        assert_eq!(hours.len(), 4);
+        assert_eq!(hours["2026-10-02 01:00"].non_free_flow_used, 0.0);
+        assert_eq!(hours["2026-10-02 02:00"].non_free_flow_used, 0.5);
+    }
+
+    #[test]
+    fn reset() {
+        let old = UsageDelta {
+            sum_flow_used: 3.0,
+            non_free_flow_used: 3.0,
+            sum_voice_used: 20,
+            ..Default::default()
+        };
+        let new = UsageDelta {
+            sum_flow_used: 3.5,
+            non_free_flow_used: 0.5,
+            sum_voice_used: 5,
+            ..Default::default()
+        };
+        let delta = new.since(&old);
+        assert_eq!(delta.sum_flow_used, 0.5);
+        assert_eq!(delta.non_free_flow_used, 0.5);
+        assert_eq!(delta.sum_voice_used, 5);
+    }
+
+    #[test]
+    fn paging() {
+        let readings: Vec<HistoryModel> = (0..HISTORY_PAGE_SIZE as u32 + 1)
+            .map(|hour| reading(1 + hour / 24, hour % 24, hour as f64))
+            .collect();
+        let hours = bucket_deltas(None, &readings, Granularity::Hour);
+        assert!(format_history_page(&hours, 1).ends_with("use `--page 2` to see more"));
+        assert!(format_history_page(&hours, 2).ends_with("Page 2/2"));
+    }
+}
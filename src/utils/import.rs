@@ -0,0 +1,351 @@
+use std::collections::HashSet;
+
+use clap::ValueEnum;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    Set, TransactionTrait,
+};
+use serde::Deserialize;
+
+use crate::model::{
+    history, target::ensure_private_target, ConfigEntity, ConfigModel, DailyActiveModel,
+    DailyEntity, HistoryActiveModel, HistoryEntity, LastActiveModel, LastEntity,
+};
+
+use super::export::{ExportData, EXPORT_VERSION};
+
+/// rows per insert statement of history, to stay below the sqlite variable limit
+const HISTORY_INSERT_BATCH: usize = 100;
+
+/// the message ending an import sent in several messages
+pub const IMPORT_END_MARKER: &str = "END";
+
+/// the max size of an import sent by messages, in bytes
+pub const IMPORT_MAX_SIZE: usize = 1024 * 1024;
+
+/// collect an import sent as the chunks of the text export, in one or more messages
+#[derive(Default)]
+pub struct ImportChunks {
+    text: String,
+}
+
+impl ImportChunks {
+    /// add a message, return whether the import is complete,
+    /// that is the end marker is received or the text is a whole json value
+    pub fn push(&mut self, message: &str) -> Result<bool, String> {
+        if message.trim() == IMPORT_END_MARKER {
+            return Ok(true);
+        }
+        if self.text.len() + message.len() > IMPORT_MAX_SIZE {
+            return Err(format!(
+                "The import is larger than {} KB, import the users in several parts.",
+                IMPORT_MAX_SIZE / 1024
+            ));
+        }
+        self.text += message;
+        // a json object or array is only valid when it is closed, so a prefix is never taken
+        let trimmed = self.text.trim_end();
+        Ok((trimmed.ends_with('}') || trimmed.ends_with(']'))
+            && serde_json::from_str::<serde_json::Value>(trimmed).is_ok())
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ImportMode {
+    /// Keep existing data, newer snapshots and missing history readings are added
+    Merge,
+    /// Delete the existing data of the imported users first
+    Replace,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ImportPayload {
+    One(Box<ExportData>),
+    Many(Vec<ExportData>),
+}
+
+/// the result of importing a user
+pub struct ImportReport {
+    pub user: String,
+    pub enable_task: bool,
+    pub last: bool,
+    pub daily: bool,
+    pub history: usize,
+}
+
+/// parse a single export or a list of exports
+pub fn parse_import(text: &str) -> Result<Vec<ExportData>, String> {
+    let payload: ImportPayload =
+        serde_json::from_str(text.trim()).map_err(|e| format!("Invalid import data: {}", e))?;
+    let exports = match payload {
+        ImportPayload::One(export) => vec![*export],
+        ImportPayload::Many(exports) => exports,
+    };
+    if let Some(export) = exports.iter().find(|e| e.version != EXPORT_VERSION) {
+        return Err(format!(
+            "Unsupported export version {}, expected {}.",
+            export.version, EXPORT_VERSION
+        ));
+    }
+    Ok(exports)
+}
+
+/// import the export of a user in a transaction, the caller should restart the task of the user
+pub async fn import_user(
+    db: &sea_orm::DatabaseConnection,
+    export: ExportData,
+    mode: ImportMode,
+) -> anyhow::Result<ImportReport> {
+    let user = export
+        .config
+        .get("user")
+        .and_then(|user| user.as_str())
+        .ok_or(anyhow::anyhow!("Missing user in config"))?
+        .to_string();
+
+    let txn = db.begin().await?;
+    let existing = ConfigEntity::find_by_id(user.as_str()).one(&txn).await?;
+    let base = match mode {
+        ImportMode::Merge => existing.as_ref(),
+        ImportMode::Replace => None,
+    };
+    let config = ConfigModel::from_json(&export.config, base)
+        .map_err(|errors| anyhow::anyhow!("Invalid config of {}: {}", user, errors.join(" ")))?;
+
+    if mode == ImportMode::Replace {
+        LastEntity::delete_by_id(user.as_str()).exec(&txn).await?;
+        DailyEntity::delete_by_id(user.as_str()).exec(&txn).await?;
+        HistoryEntity::delete_many()
+            .filter(history::Column::User.eq(user.as_str()))
+            .exec(&txn)
+            .await?;
+    }
+    if existing.is_some() {
+        ConfigEntity::update(config.clone().into_active_model().reset_all())
+            .exec(&txn)
+            .await?;
+    } else {
+        ConfigEntity::insert(config.clone().into_active_model())
+            .exec(&txn)
+            .await?;
+    }
+    ensure_private_target(&txn, &user, &config.bot).await?;
+
+    // snapshots are only replaced by newer ones
+    let mut report = ImportReport {
+        user: user.clone(),
+        enable_task: config.enable_task,
+        last: false,
+        daily: false,
+        history: 0,
+    };
+    if let Some(mut last) = export.last {
+        let current = LastEntity::find_by_id(user.as_str()).one(&txn).await?;
+        if current
+            .as_ref()
+            .is_none_or(|current| current.time < last.time)
+        {
+            if current.is_some() {
+                LastEntity::delete_by_id(user.as_str()).exec(&txn).await?;
+            }
+            last.user = user.clone();
+            last.bot = config.bot.clone();
+            let last_active: LastActiveModel = last.into();
+            LastEntity::insert(last_active).exec(&txn).await?;
+            report.last = true;
+        }
+    }
+    if let Some(mut daily) = export.daily {
+        let current = DailyEntity::find_by_id(user.as_str()).one(&txn).await?;
+        if current
+            .as_ref()
+            .is_none_or(|current| current.time < daily.time)
+        {
+            if current.is_some() {
+                DailyEntity::delete_by_id(user.as_str()).exec(&txn).await?;
+            }
+            daily.user = user.clone();
+            daily.bot = config.bot.clone();
+            let daily_active: DailyActiveModel = daily.into();
+            DailyEntity::insert(daily_active).exec(&txn).await?;
+            report.daily = true;
+        }
+    }
+
+    // readings with a time already stored are skipped
+    let known: HashSet<_> = HistoryEntity::find()
+        .filter(history::Column::User.eq(user.as_str()))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|reading| reading.time)
+        .collect();
+    let mut readings: Vec<HistoryActiveModel> = Vec::new();
+    let mut seen = HashSet::new();
+    for reading in export.history {
+        if known.contains(&reading.time) || !seen.insert(reading.time) {
+            continue;
+        }
+        let mut reading_active = reading.into_active_model();
+        reading_active.id = NotSet;
+        reading_active.user = Set(user.clone());
+        readings.push(reading_active);
+    }
+    report.history = readings.len();
+    for batch in readings.chunks(HISTORY_INSERT_BATCH) {
+        HistoryEntity::insert_many(batch.to_vec())
+            .exec(&txn)
+            .await?;
+    }
+
+    txn.commit().await?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+    use super::{import_user, parse_import, ImportChunks, ImportMode, IMPORT_MAX_SIZE};
+    use crate::{
+        model::{history, ConfigModel, DailyEntity, HistoryEntity, LastEntity},
+        testing::{at, fake_context, insert_config, usage},
+        utils::{
+            china_unicom::query_once,
+            export::{build_export, chunk_text, ExportData},
+        },
+    };
+
+    /// a user with the snapshots and readings at 08:00 and 09:00,
+    /// return the database, the config and the export with secrets
+    async fn exported() -> (sea_orm::DatabaseConnection, ConfigModel, ExportData) {
+        let (context, provider, _sink) = fake_context().await;
+        let config = insert_config(&context.db, "test_1").await;
+        provider
+            .push_usage(usage(at(1, 8, 0), 1.0))
+            .push_usage(usage(at(1, 9, 0), 1.01));
+        for _ in 0..2 {
+            query_once(&context.db, provider.as_ref(), &context.publishers, &config)
+                .await
+                .unwrap();
+        }
+        let export = build_export(&context.db, &config, true).await.unwrap();
+        (context.db, config, export)
+    }
+
+    async fn history_len(db: &sea_orm::DatabaseConnection) -> usize {
+        HistoryEntity::find()
+            .filter(history::Column::User.eq("test_1"))
+            .all(db)
+            .await
+            .unwrap()
+            .len()
+    }
+
+    #[test]
+    fn chunks() {
+        let text = format!(
+            "{{\n  \"users\": [\n{}\n  ]\n}}\n",
+            vec!["    \"a long line of the export\""; 200].join(",\n")
+        );
+        let mut chunks = ImportChunks::default();
+        let parts = chunk_text(&text, 500);
+        assert!(parts.len() > 1);
+        for (i, part) in parts.iter().enumerate() {
+            assert_eq!(chunks.push(part).unwrap(), i == parts.len() - 1);
+        }
+        assert_eq!(chunks.text(), text);
+
+        // an invalid json is ended by the marker
+        let mut chunks = ImportChunks::default();
+        assert!(!chunks.push("{\"broken\": ]").unwrap());
+        assert!(chunks.push(" END ").unwrap());
+
+        let mut chunks = ImportChunks::default();
+        assert!(chunks.push(&"a".repeat(IMPORT_MAX_SIZE + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn merge_snapshots() {
+        let (db, _config, mut export) = exported().await;
+        let stored = export.last.clone().unwrap();
+        let last = export.last.as_mut().unwrap();
+        last.time = at(1, 7, 0);
+        last.non_free_flow_used = 0.5;
+        let daily = export.daily.as_mut().unwrap();
+        daily.time = at(1, 10, 0);
+        daily.non_free_flow_used = 2.0;
+
+        let report = import_user(&db, export, ImportMode::Merge).await.unwrap();
+        assert!(!report.last);
+        assert!(report.daily);
+        let last = LastEntity::find_by_id("test_1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.time, stored.time);
+        assert_eq!(last.non_free_flow_used, stored.non_free_flow_used);
+        let daily = DailyEntity::find_by_id("test_1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(daily.time, at(1, 10, 0));
+        assert_eq!(daily.non_free_flow_used, 2.0);
+    }
+
+    #[tokio::test]
+    async fn dedup_history() {
+        let (db, _config, mut export) = exported().await;
+        assert_eq!(export.history.len(), 2);
+        let mut reading = export.history[1].clone();
+        reading.time = at(1, 10, 0);
+        reading.non_free_flow_used = 1.5;
+        export.history.push(reading.clone());
+        export.history.push(reading);
+
+        let report = import_user(&db, export, ImportMode::Merge).await.unwrap();
+        assert_eq!(report.history, 1);
+        assert_eq!(history_len(&db).await, 3);
+    }
+
+    #[tokio::test]
+    async fn replace_masked() {
+        let (db, config, _export) = exported().await;
+        let mut export = build_export(&db, &config, false).await.unwrap();
+        export.history.clear();
+        export.last = None;
+
+        let result = import_user(&db, export, ImportMode::Replace).await;
+        assert!(matches!(result, Err(e) if e.to_string().contains("masked")));
+        assert_eq!(history_len(&db).await, 2);
+        assert!(LastEntity::find_by_id("test_1")
+            .one(&db)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn parse_payload() {
+        let (_db, _config, export) = exported().await;
+        let mut other = export.clone();
+        other.config["user"] = "test_2".into();
+        let exports =
+            parse_import(&serde_json::to_string(&vec![export.clone(), other]).unwrap()).unwrap();
+        assert_eq!(exports.len(), 2);
+        assert_eq!(exports[1].config["user"], "test_2");
+
+        let mut future = export;
+        future.version += 1;
+        let error = parse_import(&future.to_json().unwrap()).unwrap_err();
+        assert!(error.contains("Unsupported export version"));
+        assert!(parse_import("not json").is_err());
+    }
+}
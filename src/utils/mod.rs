@@ -1,4 +1,14 @@
 pub mod china_unicom;
+pub mod credential;
 pub mod db;
+pub mod export;
+pub mod failure;
+pub mod history;
+pub mod import;
+#[cfg(feature = "oxidebot")]
 pub mod oxidebot_util;
-pub mod option_t;
\ No newline at end of file
+pub mod option_t;
+pub mod redact;
+pub mod sink;
+pub mod snooze;
+pub mod units;
@@ -24,3 +24,11 @@ where
         }
     }
 }
+
+/// display an optional value, `None` is displayed as "None"
+pub fn display_option<T: std::fmt::Display>(value: Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "None".to_string(),
+    }
+}
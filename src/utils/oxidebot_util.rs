@@ -1,4 +1,10 @@
-use oxidebot::{bot::get_bot, matcher::Matcher, source::message::MessageSegment};
+use oxidebot::{
+    api::payload::SendMessageTarget, bot::get_bot, matcher::Matcher,
+    source::message::MessageSegment,
+};
+
+/// the folder where the files are written before they are sent
+const FILE_FOLDER: &str = "./china_unicom/files";
 
 pub async fn get_user_bot_from(matcher: &Matcher) -> Option<(String, String)> {
     let user = format!("{}_{}", matcher.bot.server(), matcher.try_get_user()?.id);
@@ -10,21 +16,58 @@ pub async fn get_user_bot_from(matcher: &Matcher) -> Option<(String, String)> {
     Some((user, bot))
 }
 
-pub async fn send_message(user: &str, bot: &str, message: String) -> anyhow::Result<()> {
+/// get the group of the matcher, in the form of `{server}_{group_id}`
+pub fn get_group_from(matcher: &Matcher) -> Option<String> {
+    Some(format!(
+        "{}_{}",
+        matcher.bot.server(),
+        matcher.try_get_group()?.id
+    ))
+}
+
+async fn send_to(bot: &str, target: SendMessageTarget, message: String) -> anyhow::Result<()> {
     let (server, bot_id) = bot.split_once("_").ok_or(anyhow::anyhow!("Invalid bot"))?;
-    let (_, user_id) = user
-        .split_once("_")
-        .ok_or(anyhow::anyhow!("Invalid user"))?;
 
     let bot = get_bot(server, bot_id)
         .await
         .ok_or(anyhow::anyhow!("Bot not found"))?;
 
-    bot.send_message(
-        vec![MessageSegment::text(message)],
-        oxidebot::api::payload::SendMessageTarget::Private(user_id.to_string()),
+    bot.send_message(vec![MessageSegment::text(message)], target)
+        .await?;
+    Ok(())
+}
+
+pub async fn send_message(user: &str, bot: &str, message: String) -> anyhow::Result<()> {
+    let (_, user_id) = user
+        .split_once("_")
+        .ok_or(anyhow::anyhow!("Invalid user"))?;
+    send_to(
+        bot,
+        SendMessageTarget::Private(user_id.to_string()),
+        message,
     )
-    .await?;
+    .await
+}
+
+pub async fn send_group_message(group: &str, bot: &str, message: String) -> anyhow::Result<()> {
+    let (_, group_id) = group
+        .split_once("_")
+        .ok_or(anyhow::anyhow!("Invalid group"))?;
+    send_to(bot, SendMessageTarget::Group(group_id.to_string()), message).await
+}
+
+/// send the content as a file, the temporary file is removed after sending
+pub async fn send_file(matcher: &Matcher, file_name: &str, content: &[u8]) -> anyhow::Result<()> {
+    tokio::fs::create_dir_all(FILE_FOLDER).await?;
+    let path = std::path::Path::new(FILE_FOLDER).join(file_name);
+    tokio::fs::write(&path, content).await?;
+    let result = matcher
+        .try_send_message(vec![MessageSegment::file(
+            path.to_string_lossy().to_string(),
+        )])
+        .await;
+    let _ = tokio::fs::remove_file(&path).await;
+    result?;
     Ok(())
 }
 
@@ -0,0 +1,32 @@
+/// mask a secret, only keep the first and last 4 chars if it is long enough
+pub fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.is_empty() {
+        return "(empty)".to_string();
+    }
+    if chars.len() <= 12 {
+        return "****".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}****{}", head, tail)
+}
+
+/// whether the value is the output of [`mask_secret`]
+pub fn is_masked(value: &str) -> bool {
+    value == "(empty)" || value.contains("****")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_masked, mask_secret};
+
+    #[test]
+    fn mask() {
+        assert_eq!(mask_secret(""), "(empty)");
+        assert_eq!(mask_secret("short"), "****");
+        assert_eq!(mask_secret("0123456789abcdef"), "0123****cdef");
+        assert!(is_masked(&mask_secret("0123456789abcdef")));
+        assert!(!is_masked("0123456789abcdef"));
+    }
+}
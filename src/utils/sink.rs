@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+#[cfg(feature = "oxidebot")]
+use super::oxidebot_util::{send_group_message, send_message};
+
+/// where the messages of the tasks are sent
+#[async_trait]
+pub trait MessageSink: Send + Sync {
+    /// send to the user, in the form of `{server}_{user_id}`, by the bot
+    async fn send_private(&self, user: &str, bot: &str, message: String) -> anyhow::Result<()>;
+
+    /// send to the group, in the form of `{server}_{group_id}`, by the bot
+    async fn send_group(&self, group: &str, bot: &str, message: String) -> anyhow::Result<()>;
+}
+
+pub type SharedSink = Arc<dyn MessageSink>;
+
+/// the default sink, send by the bots of oxidebot
+#[cfg(feature = "oxidebot")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OxidebotSink;
+
+#[cfg(feature = "oxidebot")]
+#[async_trait]
+impl MessageSink for OxidebotSink {
+    async fn send_private(&self, user: &str, bot: &str, message: String) -> anyhow::Result<()> {
+        send_message(user, bot, message).await
+    }
+
+    async fn send_group(&self, group: &str, bot: &str, message: String) -> anyhow::Result<()> {
+        send_group_message(group, bot, message).await
+    }
+}
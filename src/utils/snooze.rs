@@ -0,0 +1,73 @@
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta};
+
+use crate::model::config::MAX_TIMEOUT;
+
+use super::units::HumanDuration;
+
+/// parse the snooze time, a duration like `2h` or `until 08:00` / `until 2026-10-20 08:00`,
+/// a time of day is the next one after `now`
+pub fn parse_snooze(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let text = text.trim();
+    let until = match text.strip_prefix("until") {
+        Some(time) => {
+            let time = time.trim();
+            if let Ok(time) = NaiveTime::parse_from_str(time, "%H:%M") {
+                let today = now.date_naive().and_time(time);
+                let next = if today > now.naive_local() {
+                    today
+                } else {
+                    today + TimeDelta::days(1)
+                };
+                local(next)?
+            } else if let Ok(time) = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M") {
+                local(time)?
+            } else {
+                return Err(format!(
+                    "Invalid time: {}, expected HH:MM or YYYY-MM-DD HH:MM.",
+                    time
+                ));
+            }
+        }
+        None => now + TimeDelta::seconds(text.parse::<HumanDuration>()?.0),
+    };
+    if until <= now {
+        return Err("The snooze time must be in the future.".to_string());
+    }
+    if until - now > TimeDelta::seconds(MAX_TIMEOUT) {
+        return Err(format!(
+            "Can not snooze longer than {}.",
+            HumanDuration(MAX_TIMEOUT)
+        ));
+    }
+    Ok(until)
+}
+
+fn local(time: NaiveDateTime) -> Result<DateTime<Local>, String> {
+    time.and_local_timezone(Local)
+        .earliest()
+        .ok_or(format!("Invalid local time: {}", time))
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Local, TimeDelta, TimeZone};
+
+    use super::parse_snooze;
+
+    #[test]
+    fn snooze() {
+        let now = Local.with_ymd_and_hms(2026, 10, 18, 22, 0, 0).unwrap();
+        assert_eq!(parse_snooze("2h", now).unwrap(), now + TimeDelta::hours(2));
+        assert_eq!(
+            parse_snooze("until 08:00", now).unwrap(),
+            Local.with_ymd_and_hms(2026, 10, 19, 8, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_snooze("until 23:30", now).unwrap(),
+            Local.with_ymd_and_hms(2026, 10, 18, 23, 30, 0).unwrap()
+        );
+        assert!(parse_snooze("until 2026-10-01 08:00", now).is_err());
+        assert!(parse_snooze("until 8", now).is_err());
+        assert!(parse_snooze("60d", now).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A duration in seconds, parsed from strings like `300`, `30s`, `30m`, `2h`, `1d` or `1h30m`.
+/// A bare number is treated as seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HumanDuration(pub i64);
+
+const DURATION_UNITS: [(char, i64); 4] = [('d', 86400), ('h', 3600), ('m', 60), ('s', 1)];
+
+impl FromStr for HumanDuration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if s.is_empty() {
+            return Err("Empty duration".to_string());
+        }
+        if let Ok(seconds) = s.parse::<i64>() {
+            return Ok(HumanDuration(seconds));
+        }
+
+        let mut total: i64 = 0;
+        let mut number = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            if c.is_whitespace() {
+                continue;
+            }
+            let (_, factor) = DURATION_UNITS
+                .iter()
+                .find(|(unit, _)| *unit == c)
+                .ok_or(format!("Unknown duration unit '{}' in '{}'", c, s))?;
+            if number.is_empty() {
+                return Err(format!("Missing number before '{}' in '{}'", c, s));
+            }
+            let value = number
+                .parse::<i64>()
+                .map_err(|e| format!("Invalid number in '{}': {}", s, e))?;
+            total = value
+                .checked_mul(*factor)
+                .and_then(|v| total.checked_add(v))
+                .ok_or(format!("Duration '{}' is too large", s))?;
+            number.clear();
+        }
+        if !number.is_empty() {
+            return Err(format!("Missing unit after '{}' in '{}'", number, s));
+        }
+        Ok(HumanDuration(total))
+    }
+}
+
+impl Display for HumanDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 <= 0 {
+            return write!(f, "{}s", self.0);
+        }
+        let mut rest = self.0;
+        for (unit, factor) in DURATION_UNITS {
+            if rest >= factor {
+                write!(f, "{}{}", rest / factor, unit)?;
+                rest %= factor;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A flow size in GB, parsed from strings like `0.5`, `500MB`, `1.5G` or `100k`.
+/// A bare number is treated as GB, units are based on 1024.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HumanSize(pub f64);
+
+impl FromStr for HumanSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        let split = lower
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+            .unwrap_or(lower.len());
+        let (number, unit) = lower.split_at(split);
+        let value = number
+            .parse::<f64>()
+            .map_err(|e| format!("Invalid size '{}': {}", s, e))?;
+        let factor = match unit.trim() {
+            "" | "g" | "gb" => 1.0,
+            "k" | "kb" => 1.0 / 1024.0 / 1024.0,
+            "m" | "mb" => 1.0 / 1024.0,
+            "t" | "tb" => 1024.0,
+            _ => return Err(format!("Unknown size unit '{}' in '{}'", unit.trim(), s)),
+        };
+        Ok(HumanSize(value * factor))
+    }
+}
+
+impl Display for HumanSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.abs() < 1.0 {
+            write!(f, "{:.2} MB", self.0 * 1024.0)
+        } else {
+            write!(f, "{:.2} GB", self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HumanDuration, HumanSize};
+
+    #[test]
+    fn parse_duration() {
+        assert_eq!("300".parse::<HumanDuration>().unwrap().0, 300);
+        assert_eq!("30m".parse::<HumanDuration>().unwrap().0, 1800);
+        assert_eq!("2h".parse::<HumanDuration>().unwrap().0, 7200);
+        assert_eq!("1d".parse::<HumanDuration>().unwrap().0, 86400);
+        assert_eq!("1h30m".parse::<HumanDuration>().unwrap().0, 5400);
+        assert!("30x".parse::<HumanDuration>().is_err());
+        assert!("h".parse::<HumanDuration>().is_err());
+        assert_eq!(HumanDuration(5400).to_string(), "1h30m");
+        assert_eq!(HumanDuration(60).to_string(), "1m");
+    }
+
+    #[test]
+    fn parse_size() {
+        assert_eq!("1.5G".parse::<HumanSize>().unwrap().0, 1.5);
+        assert_eq!("1.5gb".parse::<HumanSize>().unwrap().0, 1.5);
+        assert_eq!("0.05".parse::<HumanSize>().unwrap().0, 0.05);
+        assert_eq!("512MB".parse::<HumanSize>().unwrap().0, 0.5);
+        assert!("1.5X".parse::<HumanSize>().is_err());
+        assert_eq!(HumanSize(0.5).to_string(), "512.00 MB");
+        assert_eq!(HumanSize(1.5).to_string(), "1.50 GB");
+    }
+}